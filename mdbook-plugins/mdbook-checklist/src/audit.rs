@@ -0,0 +1,282 @@
+use crate::checklist::Checklist;
+use mdbook_preprocessor::book::Chapter;
+use mdbook_preprocessor::errors::Error;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Audit file (YAML or TOML), relative to the book root
+    file: PathBuf,
+    /// Title of the generated chapter ("Compliance report" as default)
+    title: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Status {
+    Compliant,
+    NonCompliant,
+    #[serde(rename = "n/a")]
+    NotApplicable,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Compliant => "✅ compliant",
+            Status::NonCompliant => "❌ non-compliant",
+            Status::NotApplicable => "➖ n/a",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AuditEntry {
+    pub status: Status,
+    pub justification: Option<String>,
+    #[serde(default)]
+    pub evidence: Vec<String>,
+}
+
+/// Audit entries by recommendation id
+pub(crate) type Audit = BTreeMap<String, AuditEntry>;
+
+pub(crate) fn load(config: &Config, root: &Path) -> Result<Audit, Error> {
+    let path = root.join(&config.file);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| Error::msg(format!("Cannot read audit file {}: {e}", path.display())))?;
+    let audit = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(Error::from),
+        _ => serde_yaml_ng::from_str(&content).map_err(Error::from),
+    };
+    audit.map_err(|e| Error::msg(format!("Invalid audit file {}: {e}", path.display())))
+}
+
+/// Counts of a set of entries, by status
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Stats {
+    total: usize,
+    compliant: usize,
+    non_compliant: usize,
+    not_applicable: usize,
+}
+
+impl Stats {
+    fn add(&mut self, status: Option<Status>) {
+        self.total += 1;
+        match status {
+            Some(Status::Compliant) => self.compliant += 1,
+            Some(Status::NonCompliant) => self.non_compliant += 1,
+            Some(Status::NotApplicable) => self.not_applicable += 1,
+            None => {}
+        }
+    }
+
+    fn audited(&self) -> usize {
+        self.compliant + self.non_compliant + self.not_applicable
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "audited {}/{} ({}), compliant {}/{} applicable ({})",
+            self.audited(),
+            self.total,
+            percentage(self.audited(), self.total),
+            self.compliant,
+            self.audited() - self.not_applicable,
+            percentage(self.compliant, self.audited() - self.not_applicable),
+        )
+    }
+}
+
+fn percentage(part: usize, total: usize) -> String {
+    if total == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.0}%", 100.0 * part as f64 / total as f64)
+    }
+}
+
+/// Status of an audited entry with its justification and evidence links, as
+/// shown in the generated chapters
+pub(crate) fn assessment(audit_entry: Option<&AuditEntry>) -> String {
+    let Some(audit_entry) = audit_entry else {
+        return "not audited".to_string();
+    };
+    let mut res = audit_entry.status.label().to_string();
+    if let Some(justification) = &audit_entry.justification {
+        res.push_str(&format!(": {justification}"));
+    }
+    if !audit_entry.evidence.is_empty() {
+        let evidence: Vec<String> = audit_entry
+            .evidence
+            .iter()
+            .enumerate()
+            .map(|(i, link)| format!("[{}]({})", i + 1, link))
+            .collect();
+        res.push_str(&format!(" ({})", evidence.join(", ")));
+    }
+    res
+}
+
+/// Generates the compliance report chapter: the coverage of the audit given
+/// to the checklist, followed by the checklist with the statuses of the
+/// entries. Audit entries referencing unknown ids are reported as an error.
+pub(crate) fn generate_report(config: &Config, checklist: &Checklist) -> Result<Chapter, Error> {
+    let empty = Audit::new();
+    let audit = checklist.audit().unwrap_or(&empty);
+    let known: HashSet<&str> = checklist
+        .chapters()
+        .flat_map(|(_, _, entries)| entries.iter().map(|e| &e.name as &str))
+        .collect();
+    let unknown: Vec<&str> = audit
+        .keys()
+        .map(|id| id as &str)
+        .filter(|id| !known.contains(id))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(Error::msg(format!(
+            "Audit file {} references unknown ids: {}",
+            config.file.display(),
+            unknown.join(", ")
+        )));
+    }
+
+    let title = config.title.as_deref().unwrap_or("Compliance report");
+    let mut total = Stats::default();
+    let mut coverage = String::new();
+    for (chap_name, _, entries) in checklist.chapters() {
+        let mut stats = Stats::default();
+        for entry in entries {
+            let status = audit.get(&entry.name).map(|a| a.status);
+            stats.add(status);
+            total.add(status);
+        }
+        coverage.push_str(&format!(" - {chap_name}: {}\n", stats.summary()));
+    }
+
+    let mut chapter = checklist.generate_chapter(Path::new("compliance.md"));
+    // the checklist without its title
+    let list = chapter
+        .content
+        .split_once('\n')
+        .map_or("", |(_, list)| list);
+    chapter.content = format!(
+        "# {title}\n\nOverall: {}\n\n{coverage}{list}",
+        total.summary()
+    );
+    chapter.name = title.to_string();
+    Ok(chapter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+
+    fn checklist() -> Checklist {
+        Checklist::from_entries(
+            [
+                ("devenv.md", "DENV-STABLE"),
+                ("devenv.md", "DENV-FORMAT"),
+                ("unsafe/ffi.md", "FFI-CKNONROBUST"),
+                ("unsafe/ffi.md", "FFI-NOPANIC"),
+            ]
+            .map(|(path, id)| (path, CheckEntry::new(id, Some("Rule"), "A | B"))),
+        )
+    }
+
+    fn config() -> Config {
+        Config {
+            file: PathBuf::from("audit.yaml"),
+            title: None,
+        }
+    }
+
+    #[test]
+    fn report() {
+        let audit: Audit = serde_yaml_ng::from_str(
+            r#"
+DENV-STABLE:
+  status: compliant
+  evidence: ["https://ci.example.org/1"]
+DENV-FORMAT:
+  status: non-compliant
+  justification: "legacy code"
+FFI-CKNONROBUST:
+  status: n/a
+  justification: "no FFI"
+"#,
+        )
+        .unwrap();
+        let mut checklist = checklist();
+        checklist.set_audit(Some(audit));
+        let chapter = generate_report(&config(), &checklist).unwrap();
+        assert_eq!(
+            r#"# Compliance report
+
+Overall: audited 3/4 (75%), compliant 1/2 applicable (50%)
+
+ - devenv.md: audited 2/2 (100%), compliant 1/2 applicable (50%)
+ - unsafe/ffi.md: audited 1/2 (50%), compliant 0/0 applicable (-)
+
+
+ - devenv.md:
+   - [x] Rule - A | B ([DENV-STABLE]) — ✅ compliant ([1](https://ci.example.org/1))
+   - [ ] Rule - A | B ([DENV-FORMAT]) — ❌ non-compliant: legacy code
+
+ - unsafe/ffi.md:
+   - [ ] Rule - A | B ([FFI-CKNONROBUST]) — ➖ n/a: no FFI
+   - [ ] Rule - A | B ([FFI-NOPANIC]) — not audited
+
+
+[DENV-STABLE]: devenv.md#DENV-STABLE
+[DENV-FORMAT]: devenv.md#DENV-FORMAT
+[FFI-CKNONROBUST]: unsafe/ffi.md#FFI-CKNONROBUST
+[FFI-NOPANIC]: unsafe/ffi.md#FFI-NOPANIC
+"#,
+            chapter.content
+        );
+    }
+
+    #[test]
+    fn table() {
+        let audit: Audit =
+            toml::from_str("[DENV-FORMAT]\nstatus = \"non-compliant\"\njustification = \"a | b\"")
+                .unwrap();
+        let mut checklist = checklist();
+        checklist.set_audit(Some(audit));
+        checklist.set_table_config(Some(Default::default()));
+        let chapter = checklist.generate_chapter(Path::new("checklist.md"));
+        assert!(chapter.content.contains(
+            "| [DENV-FORMAT] | Rule | A \\| B | `\\pageref{DENV-FORMAT}`{=latex} | ❌ non-compliant: a \\| b |\n"
+        ));
+        assert!(chapter.content.contains(
+            "| [FFI-NOPANIC] | Rule | A \\| B | `\\pageref{FFI-NOPANIC}`{=latex} | not audited |\n"
+        ));
+    }
+
+    #[test]
+    fn unknown_ids() {
+        let audit: Audit = toml::from_str(
+            r#"
+            [DENV-STABLE]
+            status = "compliant"
+            [DENV-UNKNOWN]
+            status = "n/a"
+            "#,
+        )
+        .unwrap();
+        let mut checklist = checklist();
+        checklist.set_audit(Some(audit));
+        let err = generate_report(&config(), &checklist).unwrap_err();
+        assert_eq!(
+            "Audit file audit.yaml references unknown ids: DENV-UNKNOWN",
+            err.to_string()
+        );
+    }
+}
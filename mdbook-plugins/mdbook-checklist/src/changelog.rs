@@ -0,0 +1,203 @@
+use crate::checklist::Checklist;
+use mdbook_preprocessor::book::Chapter;
+use mdbook_preprocessor::errors::Error;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// JSON export of a previous release, relative to the book root
+    snapshot: PathBuf,
+    /// Title of the generated chapter ("What changed" as default)
+    title: Option<String>,
+}
+
+/// An entry of a snapshot, the other exported fields being ignored
+#[derive(Debug, Deserialize)]
+pub(crate) struct SnapshotReco {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub typ: Option<String>,
+    pub title: String,
+    pub path: PathBuf,
+}
+
+pub(crate) fn load(config: &Config, root: &Path) -> Result<Vec<SnapshotReco>, Error> {
+    let path = root.join(&config.snapshot);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| Error::msg(format!("Cannot read snapshot {}: {e}", path.display())))?;
+    serde_json::from_str(&content)
+        .map_err(|e| Error::msg(format!("Invalid snapshot {}: {e}", path.display())))
+}
+
+/// Changes of the entries since the snapshot, as lines of each section
+#[derive(Debug, Default)]
+struct Changes {
+    added: Vec<String>,
+    removed: Vec<String>,
+    retitled: Vec<String>,
+    retyped: Vec<String>,
+    moved: Vec<String>,
+}
+
+fn compare(snapshot: &[SnapshotReco], checklist: &Checklist) -> Changes {
+    let previous: HashMap<&str, &SnapshotReco> = snapshot
+        .iter()
+        .map(|reco| (&reco.id as &str, reco))
+        .collect();
+    let mut current = Vec::new();
+    let mut changes = Changes::default();
+    for (_, chap_path, entries) in checklist.chapters() {
+        for entry in entries {
+            current.push(&entry.name as &str);
+            let link = format!("[{}]({}#{})", entry.name, chap_path.display(), entry.name);
+            let Some(old) = previous.get(&entry.name as &str) else {
+                changes.added.push(format!("{link}: {}", entry.desc()));
+                continue;
+            };
+            if old.title != entry.title {
+                changes
+                    .retitled
+                    .push(format!("{link}: \"{}\" → \"{}\"", old.title, entry.title));
+            }
+            if old.typ != entry.typ {
+                changes.retyped.push(format!(
+                    "{link}: {} → {}",
+                    old.typ.as_deref().unwrap_or("-"),
+                    entry.typ.as_deref().unwrap_or("-")
+                ));
+            }
+            if old.path != chap_path {
+                changes.moved.push(format!(
+                    "{link}: {} → {}",
+                    old.path.display(),
+                    chap_path.display()
+                ));
+            }
+        }
+    }
+    for reco in snapshot {
+        if !current.contains(&(&reco.id as &str)) {
+            let desc = match &reco.typ {
+                Some(typ) => format!("{typ} - {}", reco.title),
+                None => reco.title.clone(),
+            };
+            changes
+                .removed
+                .push(format!("{}: {desc} ({})", reco.id, reco.path.display()));
+        }
+    }
+    changes
+}
+
+/// Generates the chapter listing the changes since the snapshot.
+pub(crate) fn generate_chapter(
+    config: &Config,
+    snapshot: &[SnapshotReco],
+    checklist: &Checklist,
+) -> Chapter {
+    let changes = compare(snapshot, checklist);
+    let title = config.title.as_deref().unwrap_or("What changed");
+    let mut content = format!(
+        "# {title}\n\nChanges since {}.\n",
+        config.snapshot.display()
+    );
+    for (heading, lines) in [
+        ("Added", &changes.added),
+        ("Removed", &changes.removed),
+        ("Retitled", &changes.retitled),
+        ("Retyped", &changes.retyped),
+        ("Moved", &changes.moved),
+    ] {
+        content.push_str(&format!("\n## {heading}\n\n"));
+        if lines.is_empty() {
+            content.push_str("None.\n");
+        }
+        for line in lines {
+            content.push_str(&format!(" - {line}\n"));
+        }
+    }
+    Chapter::new(title, content, "changelog.md", vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+
+    #[test]
+    fn changes() {
+        let mut checklist = Checklist::new();
+        for (path, id, typ, title) in [
+            (
+                "devenv.md",
+                "DENV-STABLE",
+                "Rule",
+                "Use the stable toolchain",
+            ),
+            ("devenv.md", "DENV-FORMAT", "Rule", "Use rustfmt"),
+            (
+                "unsafe/memory.md",
+                "MEM-FORGET",
+                "Rule",
+                "Do not use forget",
+            ),
+            (
+                "errors.md",
+                "LANG-ERRDO",
+                "Recommendation",
+                "Use the ? operator",
+            ),
+        ] {
+            checklist.insert(
+                path,
+                &PathBuf::from(path),
+                CheckEntry::new(id, Some(typ), title),
+            );
+        }
+        let snapshot: Vec<SnapshotReco> = serde_json::from_str(
+            r#"[
+  {"id": "DENV-STABLE", "type": "Rule", "title": "Use the stable toolchain", "chapter": "Development environment", "path": "devenv.md", "anchor": "devenv.html#DENV-STABLE", "body": ""},
+  {"id": "DENV-FORMAT", "type": "Recommendation", "title": "Use a formatter", "path": "devenv.md"},
+  {"id": "MEM-FORGET", "type": "Rule", "title": "Do not use forget", "path": "standard.md"},
+  {"id": "LANG-NAMING", "type": "Rule", "title": "Follow the naming conventions", "path": "naming.md"}
+]"#,
+        )
+        .unwrap();
+        let config = Config {
+            snapshot: PathBuf::from("snapshots/v1.0.json"),
+            title: None,
+        };
+        let chapter = generate_chapter(&config, &snapshot, &checklist);
+        assert_eq!(
+            r#"# What changed
+
+Changes since snapshots/v1.0.json.
+
+## Added
+
+ - [LANG-ERRDO](errors.md#LANG-ERRDO): Recommendation - Use the ? operator
+
+## Removed
+
+ - LANG-NAMING: Rule - Follow the naming conventions (naming.md)
+
+## Retitled
+
+ - [DENV-FORMAT](devenv.md#DENV-FORMAT): "Use a formatter" → "Use rustfmt"
+
+## Retyped
+
+ - [DENV-FORMAT](devenv.md#DENV-FORMAT): Recommendation → Rule
+
+## Moved
+
+ - [MEM-FORGET](unsafe/memory.md#MEM-FORGET): standard.md → unsafe/memory.md
+"#,
+            chapter.content
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::checklist::Checklist;
+use mdbook_preprocessor::book::{Book, BookItem};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Title of the box ("Recommendations in this chapter" as default)
+    title: Option<String>,
+}
+
+/// Appends to every chapter with entries a box listing them, linking to their
+/// anchors in the page.
+pub(crate) fn append(book: &mut Book, config: &Config, checklist: &Checklist) {
+    let title = config
+        .title
+        .as_deref()
+        .unwrap_or("Recommendations in this chapter");
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(chapter) = item else {
+            return;
+        };
+        let Some(path) = chapter.path.as_deref() else {
+            return;
+        };
+        if let Some(list) = generate(title, checklist, path) {
+            if !chapter.content.ends_with('\n') {
+                chapter.content.push('\n');
+            }
+            chapter.content.push_str(&list);
+        }
+    });
+}
+
+fn generate(title: &str, checklist: &Checklist, path: &Path) -> Option<String> {
+    let (_, _, entries) = checklist
+        .chapters()
+        .find(|(_, chap_path, _)| *chap_path == path)?;
+    let mut list = format!("\n<div class=\"chapter-checklist\">\n\n**{title}**\n\n");
+    for entry in entries {
+        list.push_str(&format!(
+            " - [{}](#{}): {}\n",
+            entry.name,
+            entry.name,
+            entry.desc()
+        ));
+    }
+    list.push_str("\n</div>\n");
+    Some(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+    use mdbook_preprocessor::book::Chapter;
+    use std::path::PathBuf;
+
+    #[test]
+    fn chapter_list() {
+        let mut checklist = Checklist::new();
+        for (id, typ) in [("FFI-CKNONROBUST", Some("Rule")), ("FFI-NOTE", None)] {
+            checklist.insert(
+                "FFI",
+                &PathBuf::from("unsafe/ffi.md"),
+                CheckEntry::new(id, typ, &format!("Title of {id}")),
+            );
+        }
+        let chapter = |name: &str, path: &str| {
+            BookItem::Chapter(Chapter::new(name, format!("# {name}"), path, vec![]))
+        };
+        let mut book = Book::new_with_items(vec![
+            chapter("Unsafe", "unsafe.md"),
+            chapter("FFI", "unsafe/ffi.md"),
+        ]);
+        append(&mut book, &Config::default(), &checklist);
+        let contents: Vec<&str> = book.chapters().map(|c| &c.content as &str).collect();
+        assert_eq!(
+            vec![
+                "# Unsafe",
+                r#"# FFI
+
+<div class="chapter-checklist">
+
+**Recommendations in this chapter**
+
+ - [FFI-CKNONROBUST](#FFI-CKNONROBUST): Rule - Title of FFI-CKNONROBUST
+ - [FFI-NOTE](#FFI-NOTE): Title of FFI-NOTE
+
+</div>
+"#
+            ],
+            contents
+        );
+    }
+}
@@ -1,68 +1,868 @@
-use mdbook_preprocessor::book::Chapter;
-use std::path::PathBuf;
-use toml::{value::Table, Value};
+use crate::audit::{self, Audit, Status};
+use crate::crossref;
+use mdbook_preprocessor::book::{Book, Chapter, SectionNumber};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 pub struct Checklist {
     title: String,
+    types: TypesConfig,
+    filter: FilterConfig,
+    bodies: Option<BodiesConfig>,
+    table: Option<TableConfig>,
+    locations: HashMap<PathBuf, Location>,
+    /// Formatted numbers of the entries, by id
+    numbers: HashMap<String, String>,
+    /// Statuses of the entries in the audit of a project
+    audit: Option<Audit>,
     data: Vec<(String, PathBuf, Vec<CheckEntry>)>,
 }
 
+/// Location of a chapter in the structure of the book
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Location {
+    /// Title of the part of a numbered chapter
+    pub part: Option<String>,
+    /// Names of the parent chapters, outermost first
+    pub parents: Vec<String>,
+    pub number: Option<SectionNumber>,
+}
+
+/// How the entries are filtered and grouped by type in the checklist chapter
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct TypesConfig {
+    /// One list per type, false as default
+    #[serde(default)]
+    group: bool,
+    /// One list per value of another attribute, an entry with several tags
+    /// being in several lists
+    group_by: Option<GroupBy>,
+    /// Types of the entries included in the checklist (all as default)
+    include: Option<Vec<String>>,
+    /// Order of the lists, other types following in order of appearance
+    #[serde(default)]
+    order: Vec<String>,
+    /// Headings of the lists, by type (the type itself as default)
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    /// Heading of the list of the entries without type, or without value of
+    /// the grouping attribute ("Other" as default)
+    other: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum GroupBy {
+    Type,
+    Tags,
+    AppliesTo,
+    Since,
+    Tool,
+}
+
+/// How the bodies of the entries are rendered in the checklist chapter (not at
+/// all as default)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct BodiesConfig {
+    render: Render,
+    /// Label of the collapsible blocks ("Details" as default)
+    summary: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Render {
+    /// Below the entry
+    Inline,
+    /// In a collapsible `<details>` block below the entry
+    Details,
+}
+
+impl BodiesConfig {
+    /// The body of an entry, indented to be part of its list item
+    fn render(&self, body: &str, indent: &str) -> String {
+        let body = match self.render {
+            Render::Inline => body.to_string(),
+            Render::Details => format!(
+                "<details>\n<summary>{}</summary>\n\n{body}\n\n</details>",
+                self.summary.as_deref().unwrap_or("Details")
+            ),
+        };
+        let mut rendered = String::from("\n");
+        for line in body.lines() {
+            if !line.is_empty() {
+                rendered.push_str(indent);
+                rendered.push_str("     ");
+            }
+            rendered.push_str(line);
+            rendered.push('\n');
+        }
+        rendered.push('\n');
+        rendered
+    }
+}
+
+/// Table layout of the checklist chapter, for renderers without task lists
+/// (e.g. the LaTeX typesetter)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct TableConfig {
+    /// Reference to the page of an entry, as raw LaTeX kept by the typesetter,
+    /// `{id}` being replaced by its id (`` `\pageref{{id}}`{=latex} `` as
+    /// default)
+    page_ref: Option<String>,
+    /// Label of an entry, target of its page references, as raw LaTeX kept by
+    /// the typesetter, `{id}` being replaced by its id
+    /// (`` `\label{{id}}`{=latex} `` as default)
+    label: Option<String>,
+    /// Column headings ("Id", "Type", "Title", "Page" and "Compliance" as
+    /// default)
+    headers: Option<[String; 5]>,
+}
+
+impl TableConfig {
+    /// Reference to the page of the entry `id`
+    pub(crate) fn page_ref(&self, id: &str) -> String {
+        self.page_ref
+            .as_deref()
+            .unwrap_or("`\\pageref{{id}}`{=latex}")
+            .replace("{id}", id)
+    }
+
+    /// Label of the entry `id`
+    pub(crate) fn label(&self, id: &str) -> String {
+        self.label
+            .as_deref()
+            .unwrap_or("`\\label{{id}}`{=latex}")
+            .replace("{id}", id)
+    }
+}
+
+/// Entries excluded from the checklist chapter according to their metadata
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct FilterConfig {
+    /// Only entries with one of these tags are included
+    include_tags: Option<Vec<String>>,
+    /// Entries with one of these tags are excluded
+    #[serde(default)]
+    exclude_tags: Vec<String>,
+    /// Kinds of projects targeted: entries restricted to other kinds of
+    /// projects are excluded (no restriction as default)
+    applies_to: Option<Vec<String>>,
+}
+
+impl FilterConfig {
+    fn includes(&self, meta: &Metadata) -> bool {
+        let any_of = |values: &[String], accepted: &[String]| {
+            values.iter().any(|value| accepted.contains(value))
+        };
+        self.include_tags
+            .as_ref()
+            .is_none_or(|include| any_of(&meta.tags, include))
+            && !any_of(&meta.tags, &self.exclude_tags)
+            && (meta.applies_to.is_empty()
+                || self
+                    .applies_to
+                    .as_ref()
+                    .is_none_or(|kinds| any_of(&meta.applies_to, kinds)))
+    }
+}
+
+impl TypesConfig {
+    fn group_by(&self) -> Option<GroupBy> {
+        self.group_by.or(self.group.then_some(GroupBy::Type))
+    }
+
+    fn includes(&self, typ: Option<&str>) -> bool {
+        match (&self.include, typ) {
+            (None, _) => true,
+            (Some(include), Some(typ)) => include.iter().any(|t| t == typ),
+            (Some(_), None) => false,
+        }
+    }
+
+    fn label<'a>(&'a self, typ: Option<&'a str>) -> &'a str {
+        match typ {
+            Some(typ) => self.labels.get(typ).map(|l| l as &str).unwrap_or(typ),
+            None => self.other.as_deref().unwrap_or("Other"),
+        }
+    }
+}
+
 impl Checklist {
     pub fn new() -> Self {
         Checklist {
             title: "Checklist".to_string(),
+            types: TypesConfig::default(),
+            filter: FilterConfig::default(),
+            bodies: None,
+            table: None,
+            locations: HashMap::new(),
+            numbers: HashMap::new(),
+            audit: None,
             data: Vec::new(),
         }
     }
 
-    pub fn update_config(&mut self, config: &Table) {
-        if let Some(Value::String(title)) = config.get("title") {
-            self.title = title.clone();
+    pub(crate) fn set_types_config(&mut self, types: TypesConfig) {
+        self.types = types;
+    }
+
+    pub(crate) fn set_filter_config(&mut self, filter: FilterConfig) {
+        self.filter = filter;
+    }
+
+    pub(crate) fn set_bodies_config(&mut self, bodies: Option<BodiesConfig>) {
+        self.bodies = bodies;
+    }
+
+    pub(crate) fn set_table_config(&mut self, table: Option<TableConfig>) {
+        self.table = table;
+    }
+
+    /// Table layout of the checklist chapter, for the typesetter renderer
+    pub(crate) fn table_config(&self) -> Option<&TableConfig> {
+        self.table.as_ref()
+    }
+
+    pub(crate) fn set_audit(&mut self, audit: Option<Audit>) {
+        self.audit = audit;
+    }
+
+    pub(crate) fn audit(&self) -> Option<&Audit> {
+        self.audit.as_ref()
+    }
+
+    pub(crate) fn set_numbers(&mut self, numbers: HashMap<String, String>) {
+        self.numbers = numbers;
+    }
+
+    /// Records the location in the book of the chapter at `path`.
+    pub(crate) fn locate(&mut self, path: &Path, location: Location) {
+        self.locations.insert(path.to_path_buf(), location);
+    }
+
+    /// Updates the section numbers of the located chapters, once the book is
+    /// renumbered.
+    pub(crate) fn renumber(&mut self, book: &Book) {
+        for chapter in book.chapters() {
+            let Some(path) = &chapter.path else {
+                continue;
+            };
+            if let Some(location) = self.locations.get_mut(path) {
+                location.number = chapter.number.clone();
+            }
         }
     }
 
-    pub fn insert(&mut self, chap_name: &str, chap_path: &PathBuf, name: String, desc: String) {
+    pub(crate) fn set_title(&mut self, title: String) {
+        self.title = title;
+    }
+
+    pub fn insert(&mut self, chap_name: &str, chap_path: &PathBuf, entry: CheckEntry) {
         match self.data.iter_mut().find(|(_, c, _)| c == chap_path) {
             None => {
-                self.data.push((
-                    chap_name.to_string(),
-                    chap_path.clone(),
-                    vec![CheckEntry { name, desc }],
+                self.data
+                    .push((chap_name.to_string(), chap_path.clone(), vec![entry]));
+            }
+            Some((_, _, ref mut v)) => v.push(entry),
+        }
+    }
+
+    /// Iterates over the collected entries, grouped by chapter (name and path).
+    pub fn chapters(&self) -> impl Iterator<Item = (&str, &Path, &[CheckEntry])> {
+        self.data
+            .iter()
+            .map(|(name, path, entries)| (name as &str, path as &Path, entries as &[CheckEntry]))
+    }
+
+    /// Iterates over the collected entries with the path of their chapter, to
+    /// update them.
+    pub(crate) fn entries_mut(&mut self) -> impl Iterator<Item = (&Path, &mut CheckEntry)> {
+        self.data.iter_mut().flat_map(|(_, path, entries)| {
+            let path: &Path = path;
+            entries.iter_mut().map(move |entry| (path, entry))
+        })
+    }
+
+    /// Whether an entry is listed in the checklist chapter
+    fn included(&self, entry: &CheckEntry) -> bool {
+        self.types.includes(entry.typ.as_deref()) && self.filter.includes(&entry.meta)
+    }
+
+    /// Keys of the lists in order when grouped by an attribute
+    fn groups(&self, by: GroupBy) -> Vec<Option<&str>> {
+        let mut seen = Vec::new();
+        for (_, _, entries) in &self.data {
+            for entry in entries.iter().filter(|entry| self.included(entry)) {
+                for key in entry.keys(by) {
+                    if !seen.contains(&key) {
+                        seen.push(key);
+                    }
+                }
+            }
+        }
+        let mut groups: Vec<Option<&str>> = self
+            .types
+            .order
+            .iter()
+            .map(|key| Some(key as &str))
+            .filter(|key| seen.contains(key))
+            .collect();
+        for key in seen {
+            if !groups.contains(&key) {
+                groups.push(key);
+            }
+        }
+        groups
+    }
+
+    /// Pushes the entries accepted by `filter`, nested under their part title
+    /// (as a heading of level `part_level`) and parent chapters, in the
+    /// checklist chapter at `path`.
+    fn push_list<F>(
+        &self,
+        content: &mut String,
+        path: &Path,
+        filter: F,
+        with_type: bool,
+        part_level: usize,
+    ) where
+        F: Fn(&CheckEntry) -> bool,
+    {
+        let no_location = Location::default();
+        let mut part = None;
+        let mut parents: &[String] = &[];
+        for (chap_name, chap_path, entries) in &self.data {
+            let mut entries = entries.iter().filter(|entry| filter(entry)).peekable();
+            if entries.peek().is_none() {
+                continue;
+            }
+            let location = self.locations.get(chap_path).unwrap_or(&no_location);
+            if location.part.is_some() && location.part != part {
+                part = location.part.clone();
+                parents = &[];
+                content.push_str(&format!(
+                    "\n{} {}\n",
+                    "#".repeat(part_level),
+                    part.as_deref().unwrap_or_default()
+                ));
+            }
+            // parent chapters not listed yet
+            let common = parents
+                .iter()
+                .zip(&location.parents)
+                .take_while(|(a, b)| a == b)
+                .count();
+            for (depth, parent) in location.parents.iter().enumerate().skip(common) {
+                let label = match &location.number {
+                    Some(number) => {
+                        format!("{} {parent}", SectionNumber::new(&number[..=depth]))
+                    }
+                    None => parent.clone(),
+                };
+                content.push_str(&format!("\n{} - {label}\n", "  ".repeat(depth)));
+            }
+            parents = &location.parents;
+
+            let indent = "  ".repeat(parents.len());
+            let label = match &location.number {
+                Some(number) => format!("{number} {chap_name}"),
+                None => chap_name.clone(),
+            };
+            content.push_str(&format!("\n{indent} - {label}:\n"));
+            for entry in entries {
+                let desc = if with_type {
+                    entry.desc()
+                } else {
+                    entry.title.clone()
+                };
+                let number = match self.numbers.get(&entry.name) {
+                    Some(number) => format!("{number} "),
+                    None => String::new(),
+                };
+                let (checkbox, assessment) = match &self.audit {
+                    Some(audit) => {
+                        let audit_entry = audit.get(&entry.name);
+                        let checked = audit_entry.is_some_and(|a| a.status == Status::Compliant);
+                        let checkbox = if checked { "[x]" } else { "[ ]" };
+                        (checkbox, format!(" — {}", audit::assessment(audit_entry)))
+                    }
+                    None => ("[ ]", String::new()),
+                };
+                content.push_str(&format!(
+                    "{indent}   - {checkbox} {number}{} ([{}]){assessment}\n",
+                    desc, entry.name,
                 ));
+                if let (Some(bodies), false) = (&self.bodies, entry.body.is_empty()) {
+                    let body = crossref::rebase(&entry.body, chap_path, path);
+                    content.push_str(&bodies.render(&body, &indent));
+                }
             }
-            Some((_, _, ref mut v)) => v.push(CheckEntry { name, desc }),
         }
     }
 
-    pub fn generate_chapter(self) -> Chapter {
+    /// Pushes a table of the entries accepted by `filter`, in book order.
+    fn push_table<F>(&self, content: &mut String, table: &TableConfig, filter: F)
+    where
+        F: Fn(&CheckEntry) -> bool,
+    {
+        let default_headers = ["Id", "Type", "Title", "Page", "Compliance"];
+        let headers = match &table.headers {
+            Some(headers) => headers.each_ref().map(|h| h as &str),
+            None => default_headers,
+        };
+        content.push_str(&format!("\n| {} |\n", headers.join(" | ")));
+        content.push_str("|----|------|-------|------|------------|\n");
+        for (_, _, entries) in &self.data {
+            for entry in entries.iter().filter(|entry| filter(entry)) {
+                let id = match self.numbers.get(&entry.name) {
+                    Some(number) => format!("{number} [{}]", entry.name),
+                    None => format!("[{}]", entry.name),
+                };
+                let assessment = match &self.audit {
+                    Some(audit) => cell(&audit::assessment(audit.get(&entry.name))),
+                    None => String::new(),
+                };
+                content.push_str(&format!(
+                    "| {id} | {} | {} | {} | {assessment} |\n",
+                    cell(entry.typ.as_deref().unwrap_or_default()),
+                    cell(&entry.title),
+                    table.page_ref(&entry.name),
+                ));
+            }
+        }
+    }
+
+    /// Pushes the entries accepted by `filter`, as a table or as a list
+    fn push_entries<F>(
+        &self,
+        content: &mut String,
+        path: &Path,
+        filter: F,
+        with_type: bool,
+        part_level: usize,
+    ) where
+        F: Fn(&CheckEntry) -> bool,
+    {
+        match &self.table {
+            Some(table) => self.push_table(content, table, filter),
+            None => self.push_list(content, path, filter, with_type, part_level),
+        }
+    }
+
+    /// Generates the checklist chapter, to be written at `path`, with the
+    /// statuses of the entries when an audit is given.
+    pub fn generate_chapter(&self, path: &Path) -> Chapter {
+        // links are relative to the checklist chapter
+        let depth = path.components().count().saturating_sub(1);
+        let prefix = "../".repeat(depth);
+        let included = |entry: &CheckEntry| self.included(entry);
+
         let mut content = String::new();
 
         content.push_str(&format!("# {}\n\n", self.title));
 
-        for (chap_name, _, entries) in &self.data {
-            content.push_str(&format!("\n - {}:\n", chap_name,));
-            for entry in entries {
-                content.push_str(&format!("   - [ ] {} ([{}])\n", entry.desc, entry.name,));
+        if let Some(by) = self.types.group_by() {
+            for key in self.groups(by) {
+                content.push_str(&format!("\n## {}\n", self.types.label(key)));
+                let in_group =
+                    |entry: &CheckEntry| included(entry) && entry.keys(by).contains(&key);
+                self.push_entries(&mut content, path, in_group, by != GroupBy::Type, 3);
             }
+        } else {
+            self.push_entries(&mut content, path, included, true, 2);
         }
 
         content.push_str("\n\n");
         for (_, chap_path, entries) in &self.data {
-            for entry in entries {
+            for entry in entries.iter().filter(|entry| included(entry)) {
                 content.push_str(&format!(
-                    "[{}]: {}#{}\n",
+                    "[{}]: {}{}#{}\n",
                     entry.name,
+                    prefix,
                     chap_path.to_str().unwrap(),
                     entry.name,
                 ));
             }
         }
 
-        Chapter::new(&self.title, content, "checklist.md", vec![])
+        Chapter::new(&self.title, content, path, vec![])
+    }
+}
+
+/// Escapes a text to be written in a table cell
+pub(crate) fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+pub struct CheckEntry {
+    /// Identifier of the entry, also used as anchor in its chapter.
+    pub name: String,
+    /// Type of a recommendation, `None` for a `{{#check}}` mark
+    pub typ: Option<String>,
+    pub title: String,
+    /// Markdown content of the entry, as written in the chapter.
+    pub body: String,
+    /// Line of the entry in its chapter
+    pub line: usize,
+    pub meta: Metadata,
+}
+
+/// Optional attributes of a recommendation
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub tags: Vec<String>,
+    /// Kinds of projects the recommendation is restricted to
+    pub applies_to: Vec<String>,
+    /// Release of the guide introducing the recommendation
+    pub since: Option<String>,
+    /// Tool or lint enforcing the recommendation
+    pub tool: Option<String>,
+    /// Lints enforcing the recommendation, as `[tool::]lint[=level]`
+    pub lints: Vec<String>,
+    /// Clippy settings related to the lints, as `key = value`
+    pub clippy_config: Vec<String>,
+    /// Ids of related recommendations
+    pub related: Vec<String>,
+    /// Ids of the recommendations made more precise by this one
+    pub refines: Vec<String>,
+    /// Ids of the recommendations replaced by this one
+    pub supersedes: Vec<String>,
+}
+
+impl Metadata {
+    /// Ids of the related recommendations, by kind of relation
+    pub fn relations(&self) -> [(&'static str, &[String]); 3] {
+        [
+            ("related", &self.related),
+            ("refines", &self.refines),
+            ("supersedes", &self.supersedes),
+        ]
     }
 }
 
-struct CheckEntry {
-    name: String,
-    desc: String,
+impl CheckEntry {
+    /// Values of an attribute of the entry, `None` when it has none
+    fn keys(&self, by: GroupBy) -> Vec<Option<&str>> {
+        fn values(values: &[String]) -> Vec<Option<&str>> {
+            match values {
+                [] => vec![None],
+                values => values.iter().map(|v| Some(v as &str)).collect(),
+            }
+        }
+        match by {
+            GroupBy::Type => vec![self.typ.as_deref()],
+            GroupBy::Tags => values(&self.meta.tags),
+            GroupBy::AppliesTo => values(&self.meta.applies_to),
+            GroupBy::Since => vec![self.meta.since.as_deref()],
+            GroupBy::Tool => vec![self.meta.tool.as_deref()],
+        }
+    }
+
+    pub fn desc(&self) -> String {
+        match &self.typ {
+            Some(typ) => format!("{typ} - {}", self.title),
+            None => self.title.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Checklist {
+    /// Checklist of the given entries, each in the chapter at the given path
+    /// (also used as its name)
+    pub(crate) fn from_entries<'a>(
+        entries: impl IntoIterator<Item = (&'a str, CheckEntry)>,
+    ) -> Self {
+        let mut checklist = Checklist::new();
+        for (path, entry) in entries {
+            checklist.insert(path, &PathBuf::from(path), entry);
+        }
+        checklist
+    }
+}
+
+#[cfg(test)]
+impl CheckEntry {
+    /// Entry on the first line of its chapter, without body nor metadata
+    pub(crate) fn new(name: &str, typ: Option<&str>, title: &str) -> Self {
+        CheckEntry {
+            name: name.to_string(),
+            typ: typ.map(str::to_string),
+            title: title.to_string(),
+            body: String::new(),
+            line: 1,
+            meta: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checklist(types: &str) -> Checklist {
+        let mut checklist = Checklist::from_entries(
+            [
+                ("devenv.md", "DENV-STABLE", Some("Rule")),
+                ("devenv.md", "DENV-FORMAT", Some("Recommendation")),
+                ("unsafe/ffi.md", "FFI-CKNONROBUST", Some("Rule")),
+                ("unsafe/ffi.md", "NOTE-1", None),
+            ]
+            .map(|(path, id, typ)| (path, CheckEntry::new(id, typ, &format!("Title of {id}")))),
+        );
+        checklist.set_types_config(toml::from_str(types).unwrap());
+        checklist
+    }
+
+    fn tagged(types: &str, filter: &str) -> Checklist {
+        let mut checklist = checklist(types);
+        for (_, _, entries) in checklist.data.iter_mut() {
+            for entry in entries {
+                if entry.name.starts_with("FFI-") {
+                    entry.meta.tags = vec!["ffi".to_string(), "memory".to_string()];
+                    entry.meta.applies_to = vec!["safety-critical".to_string()];
+                }
+                if entry.name == "DENV-FORMAT" {
+                    entry.meta.tags = vec!["tooling".to_string()];
+                }
+            }
+        }
+        checklist.set_filter_config(toml::from_str(filter).unwrap());
+        checklist
+    }
+
+    #[test]
+    fn flat() {
+        let chapter = checklist("").generate_chapter(Path::new("checklist.md"));
+        assert_eq!(
+            r#"# Checklist
+
+
+ - devenv.md:
+   - [ ] Rule - Title of DENV-STABLE ([DENV-STABLE])
+   - [ ] Recommendation - Title of DENV-FORMAT ([DENV-FORMAT])
+
+ - unsafe/ffi.md:
+   - [ ] Rule - Title of FFI-CKNONROBUST ([FFI-CKNONROBUST])
+   - [ ] Title of NOTE-1 ([NOTE-1])
+
+
+[DENV-STABLE]: devenv.md#DENV-STABLE
+[DENV-FORMAT]: devenv.md#DENV-FORMAT
+[FFI-CKNONROBUST]: unsafe/ffi.md#FFI-CKNONROBUST
+[NOTE-1]: unsafe/ffi.md#NOTE-1
+"#,
+            chapter.content
+        );
+    }
+
+    #[test]
+    fn grouped() {
+        let types = r#"
+            group = true
+            order = ["Recommendation"]
+            [labels]
+            Rule = "Rules"
+            Recommendation = "Recommendations"
+        "#;
+        let chapter = checklist(types).generate_chapter(Path::new("appendix/checklist.md"));
+        assert_eq!(
+            r#"# Checklist
+
+
+## Recommendations
+
+ - devenv.md:
+   - [ ] Title of DENV-FORMAT ([DENV-FORMAT])
+
+## Rules
+
+ - devenv.md:
+   - [ ] Title of DENV-STABLE ([DENV-STABLE])
+
+ - unsafe/ffi.md:
+   - [ ] Title of FFI-CKNONROBUST ([FFI-CKNONROBUST])
+
+## Other
+
+ - unsafe/ffi.md:
+   - [ ] Title of NOTE-1 ([NOTE-1])
+
+
+[DENV-STABLE]: ../devenv.md#DENV-STABLE
+[DENV-FORMAT]: ../devenv.md#DENV-FORMAT
+[FFI-CKNONROBUST]: ../unsafe/ffi.md#FFI-CKNONROBUST
+[NOTE-1]: ../unsafe/ffi.md#NOTE-1
+"#,
+            chapter.content
+        );
+    }
+
+    #[test]
+    fn other_label() {
+        let chapter = checklist("group = true\nother = \"Notes\"")
+            .generate_chapter(Path::new("checklist.md"));
+        assert!(chapter
+            .content
+            .contains("## Notes\n\n - unsafe/ffi.md:\n   - [ ] Title of NOTE-1"));
+        assert!(!chapter.content.contains("## Other"));
+    }
+
+    #[test]
+    fn filtered() {
+        let chapter = checklist("include = [\"Rule\"]").generate_chapter(Path::new("checklist.md"));
+        assert_eq!(
+            r#"# Checklist
+
+
+ - devenv.md:
+   - [ ] Rule - Title of DENV-STABLE ([DENV-STABLE])
+
+ - unsafe/ffi.md:
+   - [ ] Rule - Title of FFI-CKNONROBUST ([FFI-CKNONROBUST])
+
+
+[DENV-STABLE]: devenv.md#DENV-STABLE
+[FFI-CKNONROBUST]: unsafe/ffi.md#FFI-CKNONROBUST
+"#,
+            chapter.content
+        );
+    }
+
+    #[test]
+    fn metadata_filter() {
+        let included = |checklist: Checklist| -> Vec<String> {
+            let content = checklist
+                .generate_chapter(Path::new("checklist.md"))
+                .content;
+            content
+                .lines()
+                .filter(|line| line.starts_with('['))
+                .map(|line| line[1..line.find(']').unwrap()].to_string())
+                .collect()
+        };
+        assert_eq!(
+            vec!["DENV-STABLE", "DENV-FORMAT", "NOTE-1"],
+            included(tagged("", "exclude-tags = [\"ffi\"]"))
+        );
+        assert_eq!(
+            vec!["FFI-CKNONROBUST"],
+            included(tagged("", "include-tags = [\"memory\"]"))
+        );
+        assert_eq!(
+            vec!["DENV-STABLE", "DENV-FORMAT", "NOTE-1"],
+            included(tagged("", "applies-to = [\"web\"]"))
+        );
+        assert_eq!(
+            vec!["DENV-STABLE", "DENV-FORMAT", "FFI-CKNONROBUST", "NOTE-1"],
+            included(tagged("", "applies-to = [\"safety-critical\"]"))
+        );
+    }
+
+    #[test]
+    fn grouped_by_tags() {
+        let chapter = tagged("group-by = \"tags\"\norder = [\"memory\"]", "")
+            .generate_chapter(Path::new("checklist.md"));
+        let headings: Vec<&str> = chapter
+            .content
+            .lines()
+            .filter(|line| line.starts_with("## "))
+            .collect();
+        assert_eq!(
+            vec!["## memory", "## Other", "## tooling", "## ffi"],
+            headings
+        );
+        assert!(chapter
+            .content
+            .contains("## memory\n\n - unsafe/ffi.md:\n   - [ ] Rule - Title of FFI-CKNONROBUST"));
+    }
+
+    #[test]
+    fn bodies() {
+        let mut checklist = checklist("include = [\"Recommendation\"]");
+        checklist.data[0].2[1].body =
+            "The code MUST be [formatted](#rustfmt).\n\n```sh\ncargo fmt\n```".to_string();
+        checklist.set_bodies_config(Some(toml::from_str("render = \"details\"").unwrap()));
+        let chapter = checklist.generate_chapter(Path::new("checklist.md"));
+        assert!(chapter.content.contains(
+            r#"   - [ ] Recommendation - Title of DENV-FORMAT ([DENV-FORMAT])
+
+     <details>
+     <summary>Details</summary>
+
+     The code MUST be [formatted](devenv.md#rustfmt).
+
+     ```sh
+     cargo fmt
+     ```
+
+     </details>
+
+"#
+        ));
+    }
+
+    #[test]
+    fn hierarchy() {
+        let mut checklist = checklist("");
+        let location = |part: &str, parents: &[&str], number: &[u32]| Location {
+            part: Some(part.to_string()),
+            parents: parents.iter().map(|p| p.to_string()).collect(),
+            number: Some(SectionNumber::new(number)),
+        };
+        checklist.locate(Path::new("devenv.md"), location("Lifecycle", &[], &[1]));
+        checklist.locate(
+            Path::new("unsafe/ffi.md"),
+            location("Language", &["Unsafe Rust"], &[7, 3]),
+        );
+        let chapter = checklist.generate_chapter(Path::new("checklist.md"));
+        assert!(chapter.content.starts_with(
+            r#"# Checklist
+
+
+## Lifecycle
+
+ - 1. devenv.md:
+   - [ ] Rule - Title of DENV-STABLE ([DENV-STABLE])
+   - [ ] Recommendation - Title of DENV-FORMAT ([DENV-FORMAT])
+
+## Language
+
+ - 7. Unsafe Rust
+
+   - 7.3. unsafe/ffi.md:
+     - [ ] Rule - Title of FFI-CKNONROBUST ([FFI-CKNONROBUST])
+     - [ ] Title of NOTE-1 ([NOTE-1])
+"#
+        ));
+    }
+
+    #[test]
+    fn table() {
+        let mut checklist = checklist("include = [\"Rule\"]");
+        checklist.data[0].2[0].title = "A | B".to_string();
+        checklist.set_table_config(Some(TableConfig::default()));
+        let chapter = checklist.generate_chapter(Path::new("checklist.md"));
+        assert_eq!(
+            r#"# Checklist
+
+
+| Id | Type | Title | Page | Compliance |
+|----|------|-------|------|------------|
+| [DENV-STABLE] | Rule | A \| B | `\pageref{DENV-STABLE}`{=latex} |  |
+| [FFI-CKNONROBUST] | Rule | Title of FFI-CKNONROBUST | `\pageref{FFI-CKNONROBUST}`{=latex} |  |
+
+
+[DENV-STABLE]: devenv.md#DENV-STABLE
+[FFI-CKNONROBUST]: unsafe/ffi.md#FFI-CKNONROBUST
+"#,
+            chapter.content
+        );
+    }
 }
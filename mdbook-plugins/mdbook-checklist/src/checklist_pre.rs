@@ -1,68 +1,390 @@
-use crate::checklist::Checklist;
-use mdbook_markdown::pulldown_cmark::{Tag, TagEnd};
+use crate::audit;
+use crate::changelog;
+use crate::chapter_list;
+use crate::checklist::{
+    BodiesConfig, CheckEntry, Checklist, FilterConfig, Location, Metadata, TableConfig, TypesConfig,
+};
+use crate::crossref;
+use crate::export;
+use crate::labels;
+use crate::lints;
+use crate::numbering;
+use crate::placement;
+use crate::relations;
+use crate::validation::{self, Violation};
+use mdbook_markdown::pulldown_cmark::{Event, Parser, Tag, TagEnd};
 use mdbook_preprocessor::book::{Book, BookItem, Chapter};
 use mdbook_preprocessor::errors::Error;
 use mdbook_preprocessor::{Preprocessor, PreprocessorContext};
 use quick_xml::errors::IllFormedError;
 use quick_xml::Reader;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
 
 // A preprocessor for collecting the `{{#check <name> | <description>}}` marks
-// and generating a 'checklist' chapter.
+// and the `<div class="reco">` recommendations, and generating a 'checklist'
+// chapter.
 pub struct ChecklistPre;
 
 const NAME: &str = "checklist-preprocessor";
 
+/// Renderer getting the checklist as a table
+const TYPESETTER: &str = "typesetter";
+
+/// Options of `[preprocessor.checklist]`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Title of the checklist chapter ("Checklist" as default)
+    pub title: Option<String>,
+    pub export: Option<export::Config>,
+    pub audit: Option<audit::Config>,
+    pub bodies: Option<BodiesConfig>,
+    pub changelog: Option<changelog::Config>,
+    pub chapter_list: Option<chapter_list::Config>,
+    pub lints: Option<lints::Config>,
+    pub numbering: Option<numbering::Config>,
+    #[serde(default)]
+    pub placement: placement::Config,
+    #[serde(default)]
+    pub relations: relations::Config,
+    pub typesetter: Option<TableConfig>,
+    #[serde(default)]
+    pub types: TypesConfig,
+    #[serde(default)]
+    pub filter: FilterConfig,
+    #[serde(default)]
+    pub validation: validation::Config,
+}
+
 impl Preprocessor for ChecklistPre {
     fn name(&self) -> &str {
         NAME
     }
 
+    fn supports_renderer(&self, renderer: &str) -> Result<bool, Error> {
+        Ok(renderer == "html" || renderer == TYPESETTER)
+    }
+
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
-        let mut checklist = Checklist::new();
-        if let Some(cfg) = ctx
+        let config: Config = ctx
             .config
-            .get("preprocessor.checklist")
-            .expect("Cannot read configuration")
-        {
-            checklist.update_config(&cfg);
+            .get("preprocessor.checklist")?
+            .unwrap_or_default();
+        let mut checklist = Checklist::new();
+        if let Some(title) = config.title {
+            checklist.set_title(title);
+        }
+        checklist.set_types_config(config.types);
+        checklist.set_filter_config(config.filter);
+        checklist.set_bodies_config(config.bodies);
+        // the typesetter gets the checklist as a table, labels and page
+        // references instead of the anchors, links and boxes of the HTML book
+        let typesetter = ctx.renderer == TYPESETTER;
+        if typesetter {
+            checklist.set_table_config(Some(config.typesetter.unwrap_or_default()));
+        }
+
+        let mut violations = collect(&mut book, &mut checklist);
+        violations.extend(validation::check(&config.validation, &checklist)?);
+        violations.extend(crossref::resolve(&mut book, &checklist));
+        crossref::resolve_bodies(&mut checklist);
+        validation::into_result(violations)?;
+
+        match checklist.table_config() {
+            Some(table) => labels::insert(&mut book, &checklist, table),
+            None => relations::render(&mut book, &config.relations, &checklist),
         }
+        relations::export(
+            &config.relations,
+            &checklist,
+            &ctx.root,
+            &ctx.config.build.build_dir,
+        )?;
 
-        book.for_each_mut(|section: &mut BookItem| {
-            if let BookItem::Chapter(ref mut chapter) = *section {
-                collect_and_replace(chapter, &mut checklist);
+        if let Some(numbering_config) = &config.numbering {
+            let numbers = numbering::assign(numbering_config, &checklist, &ctx.root)?;
+            if !typesetter {
+                numbering::inject(&mut book, &checklist, &numbers);
             }
-        });
+            checklist.set_numbers(numbers);
+        }
+
+        match &config.chapter_list {
+            Some(chapter_list_config) if !typesetter => {
+                chapter_list::append(&mut book, chapter_list_config, &checklist);
+            }
+            _ => {}
+        }
+
+        if let Some(export_config) = &config.export {
+            export::export(
+                export_config,
+                &checklist,
+                &ctx.root,
+                &ctx.config.build.build_dir,
+            )?;
+        }
+
+        if let Some(lints_config) = &config.lints {
+            lints::write(
+                lints_config,
+                &checklist,
+                &ctx.root,
+                &ctx.config.build.build_dir,
+            )?;
+        }
+
+        let report = match &config.audit {
+            Some(audit_config) => {
+                checklist.set_audit(Some(audit::load(audit_config, &ctx.root)?));
+                Some(audit::generate_report(audit_config, &checklist)?)
+            }
+            None => None,
+        };
 
-        let checklist_chapter = checklist.generate_chapter();
-        book.items.push(BookItem::Chapter(checklist_chapter));
+        let changes = match &config.changelog {
+            Some(changelog_config) => {
+                let snapshot = changelog::load(changelog_config, &ctx.root)?;
+                Some(changelog::generate_chapter(
+                    changelog_config,
+                    &snapshot,
+                    &checklist,
+                ))
+            }
+            None => None,
+        };
+
+        placement::insert(&mut book, &config.placement, checklist)?;
+        if let Some(report) = report {
+            book.items.push(BookItem::Chapter(report));
+        }
+        if let Some(changes) = changes {
+            book.items.push(BookItem::Chapter(changes));
+        }
 
         Ok(book)
     }
 }
 
-fn collect_and_replace(chapter: &Chapter, checklist: &mut Checklist) {
-    use mdbook_markdown::pulldown_cmark::{Event, Parser, TextMergeStream};
+/// Collects the entries of every chapter of a book, with the location of the
+/// chapters.
+pub(crate) fn collect(book: &mut Book, checklist: &mut Checklist) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut part = None;
+    for item in &mut book.items {
+        match item {
+            BookItem::PartTitle(title) => part = Some(title.clone()),
+            // prefix and suffix chapters are not numbered nor in a part
+            BookItem::Chapter(chapter) => {
+                let part = chapter.number.as_ref().and(part.as_ref());
+                collect_chapter(chapter, part, checklist, &mut violations);
+            }
+            BookItem::Separator => {}
+        }
+    }
+    violations
+}
+
+fn collect_chapter(
+    chapter: &mut Chapter,
+    part: Option<&String>,
+    checklist: &mut Checklist,
+    violations: &mut Vec<Violation>,
+) {
+    if !chapter.is_draft_chapter() {
+        let location = Location {
+            part: part.cloned(),
+            parents: chapter.parent_names.clone(),
+            number: chapter.number.clone(),
+        };
+        checklist.locate(chapter_path(chapter), location);
+    }
+    violations.extend(collect_and_replace(chapter, checklist));
+    for item in &mut chapter.sub_items {
+        if let BookItem::Chapter(sub_chapter) = item {
+            collect_chapter(sub_chapter, part, checklist, violations);
+        }
+    }
+}
+
+fn collect_and_replace(chapter: &mut Chapter, checklist: &mut Checklist) -> Vec<Violation> {
+    if chapter.is_draft_chapter() {
+        return Vec::new();
+    }
+    let path = chapter_path(chapter).to_path_buf();
+    let content = &chapter.content;
+
+    let mut violations = Vec::new();
+    let mut entries = Vec::new();
+    for (offset, entry) in collect_recos(content, &path) {
+        match entry {
+            Ok(entry) => entries.push((offset, entry)),
+            Err(violation) => violations.push(violation),
+        }
+    }
+    let code = code_ranges(content);
+    entries.extend(collect_checks(content, &code));
+    entries.sort_by_key(|(offset, _)| *offset);
+    for (_, entry) in entries {
+        checklist.insert(&chapter.name, &path, entry);
+    }
+
+    chapter.content = replace_checks(content, &code).into_owned();
+    violations
+}
+
+static CHECK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{#check\s+(?P<name>[^|}]*?)\s*\|\s*(?P<desc>[^}]*?)\s*\}\}").unwrap()
+});
+
+/// Collects the `{{#check <name> | <description>}}` marks out of the `code`
+/// ranges, with their offset.
+fn collect_checks(content: &str, code: &[Range<usize>]) -> Vec<(usize, CheckEntry)> {
+    CHECK_RE
+        .captures_iter(content)
+        .filter(|cap| !in_code(code, cap.get(0).unwrap().start()))
+        .map(|cap| {
+            let offset = cap.get(0).unwrap().start();
+            let entry = CheckEntry {
+                name: cap["name"].to_string(),
+                typ: None,
+                title: cap["desc"].to_string(),
+                body: String::new(),
+                line: line_of(content, offset),
+                meta: Default::default(),
+            };
+            (offset, entry)
+        })
+        .collect()
+}
+
+/// Replaces the `{{#check <name> | <description>}}` marks out of the `code`
+/// ranges by their anchored name.
+fn replace_checks<'a>(content: &'a str, code: &[Range<usize>]) -> Cow<'a, str> {
+    CHECK_RE.replace_all(content, |caps: &Captures| {
+        if in_code(code, caps.get(0).unwrap().start()) {
+            caps[0].to_string()
+        } else {
+            format!("<a id=\"{0}\"></a>{0}", &caps["name"])
+        }
+    })
+}
+
+/// Byte ranges of the code spans and code blocks of a chapter, where marks and
+/// references are left as is.
+pub(crate) fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    Parser::new(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(_) | Event::Start(Tag::CodeBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn in_code(code: &[Range<usize>], offset: usize) -> bool {
+    code.iter().any(|range| range.contains(&offset))
+}
 
-    let iterator = TextMergeStream::new(Parser::new(&chapter.content));
+/// Collects the recommendation `div`s of the chapter at `path`, with their
+/// offset. Malformed ones and malformed HTML are reported with their location.
+fn collect_recos(content: &str, path: &Path) -> Vec<(usize, Result<CheckEntry, Violation>)> {
+    let iterator = Parser::new(content).into_offset_iter();
 
+    let mut res = Vec::new();
     let mut html_value = String::new();
+    // Recommendation whose closing tag has not been reached yet, with the
+    // offset of its tag, the offset of its body and the number of `div` still
+    // opened.
+    let mut opened: Option<(usize, CheckEntry, usize, usize)> = None;
 
-    for event in iterator {
+    for (event, range) in iterator {
         match event {
             Event::Start(Tag::HtmlBlock) => {
                 html_value = String::new();
             }
             Event::Start(_) => {}
             Event::End(TagEnd::HtmlBlock) => {
-                let mut reader = Reader::from_str(&html_value);
-                reader.config_mut().trim_text(true);
-                for Reco { id, typ, title } in get_reco(reader) {
-                    checklist.insert(
-                        &chapter.name,
-                        chapter.path.as_ref().unwrap(),
-                        id,
-                        format!("{typ} - {title}"),
-                    );
+                let first_line = line_of(content, range.start);
+                // offsets of the opening `div` tags of the fragment
+                let opening_tags: Vec<usize> = DIV_OPEN_RE
+                    .find_iter(&html_value)
+                    .map(|m| m.start())
+                    .collect();
+                // recommendations by index of their opening tag
+                let mut recos: Vec<Option<CheckEntry>> =
+                    opening_tags.iter().map(|_| None).collect();
+                for (offset, reco) in get_reco(&html_value) {
+                    let line = first_line + html_value[..offset].matches('\n').count();
+                    match reco {
+                        Ok(Reco {
+                            typ,
+                            id,
+                            title,
+                            meta,
+                        }) => {
+                            let entry = CheckEntry {
+                                name: id,
+                                typ: Some(typ),
+                                title,
+                                body: String::new(),
+                                line,
+                                meta,
+                            };
+                            match opening_tags.iter().position(|tag| *tag == offset) {
+                                Some(index) => recos[index] = Some(entry),
+                                None => res.push((range.start, Ok(entry))),
+                            }
+                        }
+                        Err(message) => {
+                            let (column, snippet) = locate(content, line, &html_value, offset);
+                            let violation = Violation {
+                                path: path.to_path_buf(),
+                                line,
+                                column: Some(column),
+                                snippet: Some(snippet),
+                                message,
+                            };
+                            res.push((range.start, Err(violation)));
+                        }
+                    }
+                }
+                // the tags are located in the source, which may contain
+                // container prefixes (e.g. `> `) not in the fragment
+                let mut recos = recos.into_iter();
+                for tag in DIV_TAG_RE.find_iter(&content[range.clone()]) {
+                    let (start, end) = (range.start + tag.start(), range.start + tag.end());
+                    if tag.as_str().starts_with("</") {
+                        match opened.take() {
+                            Some((offset, entry, body_start, depth)) if depth > 1 => {
+                                opened = Some((offset, entry, body_start, depth - 1));
+                            }
+                            Some((offset, mut entry, body_start, _)) => {
+                                entry.body = with_definitions(&content[body_start..start], content);
+                                res.push((offset, Ok(entry)));
+                            }
+                            None => {}
+                        }
+                        continue;
+                    }
+                    let reco = recos.next().flatten();
+                    match (&mut opened, reco) {
+                        (None, Some(entry)) => opened = Some((start, entry, end, 1)),
+                        (None, None) => {}
+                        // nested recommendations have no body
+                        (Some((_, _, _, depth)), reco) => {
+                            *depth += 1;
+                            if let Some(entry) = reco {
+                                res.push((start, Ok(entry)));
+                            }
+                        }
+                    }
                 }
             }
             Event::Html(cow_str) => {
@@ -71,25 +393,169 @@ fn collect_and_replace(chapter: &Chapter, checklist: &mut Checklist) {
             _ => {}
         }
     }
+
+    if let Some((offset, mut entry, body_start, _)) = opened {
+        entry.body = with_definitions(&content[body_start..], content);
+        res.push((offset, Ok(entry)));
+    }
+    res
+}
+
+fn chapter_path(chapter: &Chapter) -> &Path {
+    chapter.path.as_deref().unwrap_or(Path::new(&chapter.name))
+}
+
+/// Line number (starting at 1) of a byte offset in a text
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// Column (starting at 1) and source line of a byte offset in an HTML block
+/// starting at `line` of a chapter. The HTML block may have been stripped of a
+/// prefix (e.g. in a quote), found back in the source line.
+fn locate(content: &str, line: usize, html: &str, offset: usize) -> (usize, String) {
+    let line_start = html[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let html_line = html[line_start..].lines().next().unwrap_or_default();
+    let source_line = content.lines().nth(line - 1).unwrap_or_default();
+    let prefix = source_line
+        .find(html_line.trim_end())
+        .map_or(0, |i| source_line[..i].chars().count());
+    let column = prefix + html[line_start..offset].chars().count() + 1;
+    (column, source_line.trim_end().to_string())
+}
+
+/// Reference link definitions
+static DEFINITION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^ {0,3}\[(?P<label>[^\]]+)\]:.*$").unwrap());
+
+/// Trimmed body of an entry, followed by the definitions of the reference
+/// links it uses when they are elsewhere in the chapter
+fn with_definitions(body: &str, content: &str) -> String {
+    let body = body.trim();
+    let mut defined: Vec<String> = DEFINITION_RE
+        .captures_iter(body)
+        .map(|caps| caps["label"].to_lowercase())
+        .collect();
+    let used = body.to_lowercase();
+    let mut res = body.to_string();
+    let mut separator = "\n\n";
+    for caps in DEFINITION_RE.captures_iter(content) {
+        let label = caps["label"].to_lowercase();
+        if defined.contains(&label) || !used.contains(&format!("[{label}]")) {
+            continue;
+        }
+        res.push_str(separator);
+        res.push_str(caps[0].trim());
+        separator = "\n";
+        defined.push(label);
+    }
+    res
+}
+
+/// Start of the opening `div` tags
+static DIV_OPEN_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<div\b").unwrap());
+
+/// Opening (up to the end of the tag, attribute values may contain `>`) and
+/// closing `div` tags
+pub(crate) static DIV_TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<div\b(?:[^>"']|"[^"]*"|'[^']*')*>|</div\s*>"#).unwrap());
+
+/// Attributes of a tag, with their value in double, single or no quotes
+static ATTRIBUTE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\s(?P<name>[^\s"'>/=]+)(?:\s*=\s*(?:"(?P<dq>[^"]*)"|'(?P<sq>[^']*)'|(?P<uq>[^\s"'=<>`]+)))?"#)
+        .unwrap()
+});
+
+/// Opening `div` tag of an element with an id
+pub(crate) struct IdTag {
+    /// Range of the tag in the chapter
+    pub(crate) range: Range<usize>,
+    /// Offset of the end of the `id` attribute
+    pub(crate) id_end: usize,
+}
+
+/// Opening `div` tags of a chapter out of code, by the value of their `id`
+/// attribute (the first tag when an id is repeated)
+pub(crate) fn id_tags(content: &str) -> HashMap<String, IdTag> {
+    let code = code_ranges(content);
+    let mut tags = HashMap::new();
+    for tag in DIV_TAG_RE.find_iter(content) {
+        if tag.as_str().starts_with("</") || in_code(&code, tag.start()) {
+            continue;
+        }
+        let attribute = ATTRIBUTE_RE
+            .captures_iter(tag.as_str())
+            .find(|caps| caps["name"].eq_ignore_ascii_case("id"));
+        let Some(caps) = attribute else {
+            continue;
+        };
+        let Some(id) = ["dq", "sq", "uq"].iter().find_map(|group| caps.name(group)) else {
+            continue;
+        };
+        tags.entry(id.as_str().to_string()).or_insert(IdTag {
+            range: tag.range(),
+            id_end: tag.start() + caps.get(0).unwrap().end(),
+        });
+    }
+    tags
 }
 
 struct Reco {
     typ: String,
     id: String,
     title: String,
+    meta: Metadata,
+}
+
+/// Values of a comma-separated attribute, commas in brackets or quotes (e.g.
+/// in a TOML array) not being separators
+fn split_list(value: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut quoted = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '[' | '{' if !quoted => depth += 1,
+            ']' | '}' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                values.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(&value[start..]);
+    values
+        .into_iter()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
-fn get_reco(mut reader: Reader<&[u8]>) -> Vec<Reco> {
+/// Extracts the recommendations of an HTML fragment, with the offset of their
+/// tag in the fragment. Tags missing a mandatory attribute are reported as
+/// errors, as well as malformed HTML, which ends the extraction.
+fn get_reco(html: &str) -> Vec<(usize, Result<Reco, String>)> {
     use quick_xml::events::Event;
+    let mut reader = Reader::from_str(html);
+    reader.config_mut().trim_text(true);
+    // void elements such as `<br>` are never closed in HTML
+    reader.config_mut().check_end_names = false;
     let mut res = Vec::new();
     loop {
         let event = reader.read_event();
-        //eprintln!("xml event: {event:?}");
         match event {
             Err(quick_xml::Error::IllFormed(IllFormedError::UnmatchedEndTag(_))) => {
                 //html extraction from markdown does not respect closing tag
             }
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
+            Err(e) => {
+                let offset = reader.error_position() as usize;
+                res.push((offset, Err(format!("malformed HTML: {e}"))));
+                return res;
+            }
             Ok(Event::Eof) => return res,
 
             Ok(Event::Start(e)) if e.local_name().as_ref() == b"div" => {
@@ -100,37 +566,52 @@ fn get_reco(mut reader: Reader<&[u8]>) -> Vec<Reco> {
                     .and_then(|attr| attr.unescape_value().ok())
                 {
                     if class == "reco" {
-                        let typ = e
-                            .html_attributes()
-                            .filter_map(|attr| attr.ok())
-                            .find(|attr| attr.key.local_name().as_ref() == b"type")
-                            .and_then(|attr| attr.unescape_value().ok());
-                        let title = e
-                            .html_attributes()
-                            .filter_map(|attr| attr.ok())
-                            .find(|attr| attr.key.local_name().as_ref() == b"title")
-                            .and_then(|attr| attr.unescape_value().ok());
-                        let id = e
-                            .html_attributes()
-                            .filter_map(|attr| attr.ok())
-                            .find(|attr| attr.key.local_name().as_ref() == b"id")
-                            .and_then(|attr| attr.unescape_value().ok());
-                        match (id, typ, title) {
+                        let attribute = |name: &[u8]| {
+                            e.html_attributes()
+                                .filter_map(|attr| attr.ok())
+                                .find(|attr| attr.key.local_name().as_ref() == name)
+                                .and_then(|attr| attr.unescape_value().ok())
+                                .map(|value| value.into_owned())
+                        };
+                        let list = |name: &[u8]| {
+                            attribute(name)
+                                .map(|value| split_list(&value))
+                                .unwrap_or_default()
+                        };
+                        let typ = attribute(b"type");
+                        let title = attribute(b"title");
+                        let id = attribute(b"id");
+                        let meta = Metadata {
+                            tags: list(b"tags"),
+                            applies_to: list(b"applies-to"),
+                            since: attribute(b"since"),
+                            tool: attribute(b"tool"),
+                            lints: list(b"lints"),
+                            clippy_config: list(b"clippy-config"),
+                            related: list(b"related"),
+                            refines: list(b"refines"),
+                            supersedes: list(b"supersedes"),
+                        };
+                        let end = reader.buffer_position() as usize;
+                        let offset = html[..end].rfind('<').unwrap_or_default();
+                        let reco = match (id, typ, title) {
                             (None, _, _) => {
-                                eprintln!("Recommendation div tag without \"id\" attribute")
-                            }
-                            (_, None, _) => {
-                                eprintln!("Recommendation div tag without \"type\" attribute")
+                                Err("recommendation div tag without \"id\" attribute".to_string())
                             }
-                            (_, _, None) => {
-                                eprintln!("Recommendation div tag without \"title\" attribute")
-                            }
-                            (Some(id), Some(typ), Some(title)) => res.push(Reco {
-                                typ: typ.to_string(),
-                                id: id.to_string(),
-                                title: title.to_string(),
+                            (Some(id), None, _) => Err(format!(
+                                "recommendation \"{id}\" without \"type\" attribute"
+                            )),
+                            (Some(id), _, None) => Err(format!(
+                                "recommendation \"{id}\" without \"title\" attribute"
+                            )),
+                            (Some(id), Some(typ), Some(title)) => Ok(Reco {
+                                typ,
+                                id,
+                                title,
+                                meta,
                             }),
-                        }
+                        };
+                        res.push((offset, reco));
                     }
                 }
             }
@@ -138,3 +619,352 @@ fn get_reco(mut reader: Reader<&[u8]>) -> Vec<Reco> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::records;
+
+    fn chapter(content: &str) -> Chapter {
+        Chapter::new("Chapter 1", content.to_string(), "chapter_1.md", vec![])
+    }
+
+    /// Preprocessor input of a book of one chapter, for the `renderer`
+    fn book_input(renderer: &str, content: &str) -> Vec<u8> {
+        let input = serde_json::json!([
+            {
+                "root": "/path/to/book",
+                "config": {
+                    "book": {
+                        "language": "en",
+                        "src": "src",
+                        "title": "TITLE"
+                    },
+                    "preprocessor": {
+                        "checklist": {
+                            "numbering": {},
+                            "chapter-list": {}
+                        }
+                    }
+                },
+                "renderer": renderer,
+                "mdbook_version": "0.5.2"
+            },
+            {
+                "items": [
+                    {
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": content,
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }
+                ]
+            }
+        ]);
+        serde_json::to_vec(&input).unwrap()
+    }
+
+    #[test]
+    fn typesetter() {
+        let content = r#"<div class="reco" id="DENV-STABLE" type="Rule" title="Stable" related="DENV-FORMAT">
+
+Body.
+
+</div>
+
+{{#check DENV-FORMAT | Format}}, see {{#reco DENV-STABLE}}.
+"#;
+        let (ctx, book) =
+            mdbook_preprocessor::parse_input(&book_input(TYPESETTER, content)[..]).unwrap();
+        let book = ChecklistPre.run(&ctx, book).unwrap();
+        let chapters: Vec<&Chapter> = book.chapters().collect();
+        assert_eq!(
+            r#"
+`\label{DENV-STABLE}`{=latex}
+
+<div class="reco" id="DENV-STABLE" type="Rule" title="Stable" related="DENV-FORMAT">
+
+Body.
+
+</div>
+
+`\label{DENV-FORMAT}`{=latex}DENV-FORMAT, see DENV-STABLE (`\pageref{DENV-STABLE}`{=latex}).
+"#,
+            chapters[0].content
+        );
+        assert_eq!(
+            r#"# Checklist
+
+
+| Id | Type | Title | Page | Compliance |
+|----|------|-------|------|------------|
+| R1 [DENV-STABLE] | Rule | Stable | `\pageref{DENV-STABLE}`{=latex} |  |
+| [DENV-FORMAT] |  | Format | `\pageref{DENV-FORMAT}`{=latex} |  |
+
+
+[DENV-STABLE]: chapter_1.md#DENV-STABLE
+[DENV-FORMAT]: chapter_1.md#DENV-FORMAT
+"#,
+            chapters[1].content
+        );
+    }
+
+    #[test]
+    fn config() {
+        let config: Config = toml::from_str(
+            r#"
+            title = "Recommendations checklist"
+            renderers = ["html"]
+
+            [placement]
+            part = "Lifecycle"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(Some("Recommendations checklist"), config.title.as_deref());
+        assert!(config.export.is_none());
+        assert!(config.audit.is_none());
+        assert!(config.changelog.is_none());
+    }
+
+    #[test]
+    fn collect_body() {
+        let content = r#"# Title
+
+<div class="reco" id="RECO-1" type="Rule" title="First rule">
+
+Some text that MUST be followed.
+
+<div class="warning">
+
+A nested block.
+
+</div>
+
+</div>
+
+Blah blah.
+
+<div class="reco" id="RECO-2" type="Recommendation" title="Second">
+</div>
+"#;
+        let mut checklist = Checklist::new();
+        collect_and_replace(&mut chapter(content), &mut checklist);
+        let records = records(&checklist);
+        assert_eq!(2, records.len());
+        assert_eq!("RECO-1", records[0].id);
+        assert_eq!(
+            "Some text that MUST be followed.\n\n<div class=\"warning\">\n\nA nested block.\n\n</div>",
+            records[0].body
+        );
+        assert_eq!("RECO-2", records[1].id);
+        assert_eq!(Some("Recommendation"), records[1].typ);
+        assert_eq!("", records[1].body);
+    }
+
+    #[test]
+    fn body_definitions() {
+        let content = r#"# Title
+
+<div class="reco" id="RECO-1" type="Rule" title="First rule">
+
+See [the RFC][rfc] and [Drop].
+
+[Drop]: https://doc.rust-lang.org/std/ops/trait.Drop.html
+
+</div>
+
+[RFC]: https://rust-lang.github.io/rfcs/
+[other]: other.md
+"#;
+        let recos = collect_recos(content, Path::new("chapter_1.md"));
+        assert_eq!(
+            "See [the RFC][rfc] and [Drop].\n\n\
+             [Drop]: https://doc.rust-lang.org/std/ops/trait.Drop.html\n\n\
+             [RFC]: https://rust-lang.github.io/rfcs/",
+            recos[0].1.as_ref().unwrap().body
+        );
+    }
+
+    #[test]
+    fn adjacent_recos() {
+        let content = r#"# Title
+
+<div class="reco" id="A-1" type="Rule" title="First">
+
+Body of A-1.
+
+</div>
+<div class="reco" id="A-2" type="Rule" title="Second">
+
+Body of A-2.
+
+</div>
+"#;
+        let recos: Vec<CheckEntry> = collect_recos(content, Path::new("chapter_1.md"))
+            .into_iter()
+            .map(|(_, reco)| reco.unwrap())
+            .collect();
+        assert_eq!(
+            vec![("A-1", "Body of A-1."), ("A-2", "Body of A-2.")],
+            recos
+                .iter()
+                .map(|entry| (&entry.name as &str, &entry.body as &str))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(8, recos[1].line);
+    }
+
+    #[test]
+    fn missing_attributes() {
+        let content = r#"# Title
+
+<div class="reco" id="RECO-1" title="No type">
+
+</div>
+
+<div class="reco" type="Rule" title="No id">
+</div>
+
+<div class="reco" id="RECO-3" type="Rule" title="Valid">
+</div>
+"#;
+        let mut checklist = Checklist::new();
+        let violations = collect_and_replace(&mut chapter(content), &mut checklist);
+        assert_eq!(
+            vec![
+                "chapter_1.md:3:1: recommendation \"RECO-1\" without \"type\" attribute\n    \
+                 <div class=\"reco\" id=\"RECO-1\" title=\"No type\">",
+                "chapter_1.md:7:1: recommendation div tag without \"id\" attribute\n    \
+                 <div class=\"reco\" type=\"Rule\" title=\"No id\">",
+            ],
+            violations.iter().map(|v| v.to_string()).collect::<Vec<_>>()
+        );
+        let records = records(&checklist);
+        assert_eq!(1, records.len());
+        assert_eq!("RECO-3", records[0].id);
+    }
+
+    #[test]
+    fn metadata() {
+        let content = r#"<div class="reco" id="FFI-CKNONROBUST" type="Rule" title="Check values"
+     tags="ffi, memory" applies-to="safety-critical" since="v1.2" tool="clippy::mem_forget">
+</div>
+
+<div class="reco" id="LANG-NAMING" type="Rule" title="Naming">
+</div>
+"#;
+        let mut checklist = Checklist::new();
+        collect_and_replace(&mut chapter(content), &mut checklist);
+        let entries = checklist.chapters().next().unwrap().2;
+        assert_eq!(
+            Metadata {
+                tags: vec!["ffi".to_string(), "memory".to_string()],
+                applies_to: vec!["safety-critical".to_string()],
+                since: Some("v1.2".to_string()),
+                tool: Some("clippy::mem_forget".to_string()),
+                ..Default::default()
+            },
+            entries[0].meta
+        );
+        assert_eq!(Metadata::default(), entries[1].meta);
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            vec!["a", "b = [\"x\", \"y\"]", "c = \"1,2\""],
+            split_list(" a,, b = [\"x\", \"y\"], c = \"1,2\" ")
+        );
+    }
+
+    #[test]
+    fn malformed_html() {
+        let content = r#"# Title
+
+> <div class="reco" id="RECO-1" type="Rule" title="Quoted">
+>
+> </div>
+
+<div class="reco" id="RECO-2" type="Rule"
+     title="Multi-line tag">
+</div>
+
+<div class="warning">
+    <p>Unclosed comment <!-- here
+</div>
+"#;
+        let mut checklist = Checklist::new();
+        let violations = collect_and_replace(&mut chapter(content), &mut checklist);
+        assert_eq!(
+            vec![
+                "chapter_1.md:12:25: malformed HTML: syntax error: comment not closed: \
+                 `-->` not found before end of input\n        <p>Unclosed comment <!-- here"
+            ],
+            violations.iter().map(|v| v.to_string()).collect::<Vec<_>>()
+        );
+        let records = records(&checklist);
+        assert_eq!(2, records.len());
+        assert_eq!(7, checklist.chapters().next().unwrap().2[1].line);
+    }
+
+    #[test]
+    fn check_marks() {
+        let content = r#"# Chapter 1
+
+> ### Reco {{#check RECO-1 | A small description for recommendation 1}}
+>
+> This should be a lengthy description of recommendation 1.
+
+<div class="reco" id="RECO-2" type="Rule" title="A rule">
+
+Blah blah.
+
+</div>
+
+> ### Reco {{#check RECO-3|Some recommendation to be checked}}
+"#;
+        let mut chapter = chapter(content);
+        let mut checklist = Checklist::new();
+        assert!(collect_and_replace(&mut chapter, &mut checklist).is_empty());
+        let records = records(&checklist);
+        let summary: Vec<_> = records.iter().map(|r| (r.id, r.typ, r.title)).collect();
+        assert_eq!(
+            vec![
+                ("RECO-1", None, "A small description for recommendation 1"),
+                ("RECO-2", Some("Rule"), "A rule"),
+                ("RECO-3", None, "Some recommendation to be checked"),
+            ],
+            summary
+        );
+        assert!(chapter
+            .content
+            .contains("> ### Reco <a id=\"RECO-1\"></a>RECO-1\n"));
+        assert!(chapter
+            .content
+            .contains("> ### Reco <a id=\"RECO-3\"></a>RECO-3\n"));
+        assert!(!chapter.content.contains("{{#check"));
+    }
+
+    #[test]
+    fn check_marks_in_code() {
+        let content = r#"# Chapter 1
+
+Marks are written `{{#check <name> | <description>}}`, e.g.
+
+```markdown
+{{#check RECO-1 | A description}}
+```
+"#;
+        let mut chapter = chapter(content);
+        let mut checklist = Checklist::new();
+        assert!(collect_and_replace(&mut chapter, &mut checklist).is_empty());
+        assert!(records(&checklist).is_empty());
+        assert_eq!(content, chapter.content);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::checklist::{CheckEntry, Checklist, TableConfig};
+use crate::checklist_pre::{code_ranges, in_code};
+use crate::validation::Violation;
+use mdbook_preprocessor::book::{Book, BookItem};
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+// Cross-references to the entries, resolved once every chapter has been
+// collected (code spans and code blocks are left as is):
+// - `{{#reco ID}}` is replaced by a link to the entry, `{{#reco ID title}}`
+//   also shows its title;
+// - `[](reco:ID)` is a link to the entry, its text being the id when empty.
+// With the typesetter renderer, the text is followed by the page reference of
+// the entry instead of being a link.
+
+static RECO_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{#reco\s+(?P<id>[^\s}]+)\s*(?P<title>title)?\s*\}\}").unwrap()
+});
+
+static LINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[(?P<text>[^\]]*)\]\(reco:(?P<id>[^)\s]+)\)").unwrap());
+
+/// Links (inline or reference definitions) with a destination, possibly
+/// relative
+static URL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)(?P<before>\]\(|^ {0,3}\[[^\]]+\]:[ \t]*)(?P<url>[^)\s]+)").unwrap()
+});
+
+/// Entries by id, with the path of their chapter
+fn by_id(checklist: &Checklist) -> HashMap<&str, (&Path, &CheckEntry)> {
+    checklist
+        .chapters()
+        .flat_map(|(_, path, entries)| entries.iter().map(move |e| (&e.name as &str, (path, e))))
+        .collect()
+}
+
+/// Resolves the cross-references of every chapter of a book. References to
+/// unknown ids are reported.
+pub(crate) fn resolve(book: &mut Book, checklist: &Checklist) -> Vec<Violation> {
+    let entries = by_id(checklist);
+    let table = checklist.table_config();
+    let mut violations = Vec::new();
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(chapter) = item else {
+            return;
+        };
+        let Some(path) = chapter.path.clone() else {
+            return;
+        };
+        let mut unknown = Vec::new();
+        let content = replace(&chapter.content, &path, &entries, table, &mut unknown);
+        for (offset, id) in unknown {
+            violations.push(Violation {
+                path: path.clone(),
+                line: chapter.content[..offset].matches('\n').count() + 1,
+                message: format!("reference to unknown recommendation \"{id}\""),
+                ..Default::default()
+            });
+        }
+        chapter.content = content;
+    });
+    violations
+}
+
+/// Resolves the cross-references in the bodies of the entries, relative to
+/// their chapter. References to unknown ids are reported with the chapters.
+pub(crate) fn resolve_bodies(checklist: &mut Checklist) {
+    let entries = by_id(checklist);
+    let table = checklist.table_config();
+    let bodies: Vec<String> = checklist
+        .chapters()
+        .flat_map(|(_, path, chapter_entries)| {
+            chapter_entries
+                .iter()
+                .map(|entry| replace(&entry.body, path, &entries, table, &mut Vec::new()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    for ((_, entry), body) in checklist.entries_mut().zip(bodies) {
+        entry.body = body;
+    }
+}
+
+/// Replaces the references out of code in the content of the chapter at
+/// `path`, with page references given the `table` layout of the typesetter.
+/// The unknown ids are pushed with their offset.
+fn replace(
+    content: &str,
+    path: &Path,
+    entries: &HashMap<&str, (&Path, &CheckEntry)>,
+    table: Option<&TableConfig>,
+    unknown: &mut Vec<(usize, String)>,
+) -> String {
+    let mut link = |caps: &Captures, text: &str| {
+        let id = &caps["id"];
+        match (entries.get(id), table) {
+            (Some(_), Some(table)) => format!("{text} ({})", table.page_ref(id)),
+            (Some((target, _)), None) => format!("[{text}]({})", href(path, target, id)),
+            (None, _) => {
+                unknown.push((caps.get(0).unwrap().start(), id.to_string()));
+                caps[0].to_string()
+            }
+        }
+    };
+    let code = code_ranges(content);
+    let content = RECO_RE.replace_all(content, |caps: &Captures| {
+        if in_code(&code, caps.get(0).unwrap().start()) {
+            return caps[0].to_string();
+        }
+        let id = &caps["id"];
+        let text = match (caps.name("title"), entries.get(id)) {
+            (Some(_), Some((_, entry))) => format!("{id} ({})", entry.title),
+            _ => id.to_string(),
+        };
+        link(caps, &text)
+    });
+    let code = code_ranges(&content);
+    let content = LINK_RE.replace_all(&content, |caps: &Captures| {
+        if in_code(&code, caps.get(0).unwrap().start()) {
+            return caps[0].to_string();
+        }
+        let text = match &caps["text"] {
+            "" => caps["id"].to_string(),
+            text => text.to_string(),
+        };
+        link(caps, &text)
+    });
+    content.into_owned()
+}
+
+/// Link from the chapter at `from` to the anchor `id` of the chapter at `to`
+pub(crate) fn href(from: &Path, to: &Path, id: &str) -> String {
+    if from == to {
+        return format!("#{id}");
+    }
+    let depth = from.components().count().saturating_sub(1);
+    format!("{}{}#{id}", "../".repeat(depth), to.display())
+}
+
+/// Rewrites the relative links of a piece of the chapter at `from`, to be
+/// copied in the chapter at `to`.
+pub(crate) fn rebase(content: &str, from: &Path, to: &Path) -> String {
+    if from == to {
+        return content.to_string();
+    }
+    URL_RE
+        .replace_all(content, |caps: &Captures| {
+            format!("{}{}", &caps["before"], rebase_url(&caps["url"], from, to))
+        })
+        .into_owned()
+}
+
+fn rebase_url(url: &str, from: &Path, to: &Path) -> String {
+    // absolute paths and URLs with a scheme are kept
+    let scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.contains(['/', '#', '?']));
+    if url.starts_with('/') || scheme {
+        return url.to_string();
+    }
+    let (path, fragment) = url.split_at(url.find('#').unwrap_or(url.len()));
+    let target: Vec<String> = if path.is_empty() {
+        from.components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect()
+    } else {
+        let mut target: Vec<String> = from
+            .parent()
+            .into_iter()
+            .flat_map(|dir| dir.components())
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." if target.last().is_some_and(|last| last != "..") => {
+                    target.pop();
+                }
+                segment => target.push(segment.to_string()),
+            }
+        }
+        target
+    };
+    let depth = to.components().count().saturating_sub(1);
+    format!("{}{}{fragment}", "../".repeat(depth), target.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook_preprocessor::book::Chapter;
+    use std::path::PathBuf;
+
+    #[test]
+    fn references() {
+        let mut checklist = Checklist::new();
+        for (path, id) in [
+            ("devenv.md", "DENV-STABLE"),
+            ("unsafe/ffi.md", "FFI-NOPANIC"),
+        ] {
+            checklist.insert(
+                path,
+                &PathBuf::from(path),
+                CheckEntry::new(id, Some("Rule"), &format!("Title of {id}")),
+            );
+        }
+        let content = "See {{#reco DENV-STABLE}}, {{#reco FFI-NOPANIC title}}\n\
+                       and [this rule](reco:FFI-NOPANIC) or [](reco:DENV-STABLE).\n\
+                       But not {{#reco DENV-UNKNOWN}}.\n";
+        let mut book = Book::new_with_items(vec![BookItem::Chapter(Chapter::new(
+            "FFI",
+            content.to_string(),
+            "unsafe/ffi.md",
+            vec![],
+        ))]);
+        let violations = resolve(&mut book, &checklist);
+        assert_eq!(
+            vec!["unsafe/ffi.md:3: reference to unknown recommendation \"DENV-UNKNOWN\""],
+            violations.iter().map(|v| v.to_string()).collect::<Vec<_>>()
+        );
+        let chapter = book.chapters().next().unwrap();
+        assert_eq!(
+            "See [DENV-STABLE](../devenv.md#DENV-STABLE), \
+             [FFI-NOPANIC (Title of FFI-NOPANIC)](#FFI-NOPANIC)\n\
+             and [this rule](#FFI-NOPANIC) or [DENV-STABLE](../devenv.md#DENV-STABLE).\n\
+             But not {{#reco DENV-UNKNOWN}}.\n",
+            chapter.content
+        );
+    }
+
+    #[test]
+    fn page_references() {
+        let mut checklist = Checklist::from_entries([(
+            "devenv.md",
+            CheckEntry::new("DENV-STABLE", Some("Rule"), "Title"),
+        )]);
+        checklist.set_table_config(Some(TableConfig::default()));
+        let content = "See {{#reco DENV-STABLE title}} and [this rule](reco:DENV-STABLE).\n";
+        let mut book = Book::new_with_items(vec![BookItem::Chapter(Chapter::new(
+            "FFI",
+            content.to_string(),
+            "unsafe/ffi.md",
+            vec![],
+        ))]);
+        assert!(resolve(&mut book, &checklist).is_empty());
+        assert_eq!(
+            "See DENV-STABLE (Title) (`\\pageref{DENV-STABLE}`{=latex}) \
+             and this rule (`\\pageref{DENV-STABLE}`{=latex}).\n",
+            book.chapters().next().unwrap().content
+        );
+    }
+
+    #[test]
+    fn references_in_code() {
+        let checklist = Checklist::new();
+        let content = "Write `{{#reco ID}}` or `[](reco:ID)`:\n\n\
+                       ```markdown\nSee {{#reco ID title}} and [this rule](reco:ID).\n```\n";
+        let mut book = Book::new_with_items(vec![BookItem::Chapter(Chapter::new(
+            "Syntax",
+            content.to_string(),
+            "syntax.md",
+            vec![],
+        ))]);
+        assert!(resolve(&mut book, &checklist).is_empty());
+        assert_eq!(content, book.chapters().next().unwrap().content);
+    }
+
+    #[test]
+    fn rebased() {
+        let content = "See [this](#cyclic-rc-pointer), [that](../devenv.md#DENV-STABLE)\n\
+                       and ![a figure](img/ffi.png), not [this](https://www.rust-lang.org/).\n\n\
+                       [ref]: ./memory.md\n\
+                       [mail]: mailto:someone@example.org\n";
+        assert_eq!(
+            "See [this](unsafe/ffi.md#cyclic-rc-pointer), [that](devenv.md#DENV-STABLE)\n\
+             and ![a figure](unsafe/img/ffi.png), not [this](https://www.rust-lang.org/).\n\n\
+             [ref]: unsafe/memory.md\n\
+             [mail]: mailto:someone@example.org\n",
+            rebase(
+                content,
+                Path::new("unsafe/ffi.md"),
+                Path::new("checklist.md")
+            )
+        );
+        assert_eq!(
+            "[this](../unsafe/ffi.md#x)\n\n[ref]: ../devenv.md\n",
+            rebase(
+                "[this](#x)\n\n[ref]: ../devenv.md\n",
+                Path::new("unsafe/ffi.md"),
+                Path::new("appendix/checklist.md")
+            )
+        );
+    }
+
+    #[test]
+    fn bodies() {
+        let mut checklist = Checklist::new();
+        for (path, id) in [
+            ("devenv.md", "DENV-STABLE"),
+            ("unsafe/ffi.md", "FFI-NOPANIC"),
+        ] {
+            checklist.insert(
+                path,
+                &PathBuf::from(path),
+                CheckEntry::new(id, Some("Rule"), &format!("Title of {id}")),
+            );
+        }
+        checklist.entries_mut().last().unwrap().1.body = "See {{#reco DENV-STABLE}}.".to_string();
+        resolve_bodies(&mut checklist);
+        let (_, _, entries) = checklist.chapters().last().unwrap();
+        assert_eq!(
+            "See [DENV-STABLE](../devenv.md#DENV-STABLE).",
+            entries[0].body
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use crate::checklist::{cell, Checklist};
+use mdbook_preprocessor::errors::Error;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Formats of the generated files
+    formats: Vec<Format>,
+    /// Output directory, relative to the book root (see [`output_dir`])
+    dir: Option<PathBuf>,
+    /// Base name of the generated files ("checklist" as default)
+    file_stem: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    Json,
+    Yaml,
+    Csv,
+    Markdown,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Csv => "csv",
+            Format::Markdown => "md",
+        }
+    }
+}
+
+/// A flat view of a checklist entry, as written in the exported files.
+#[derive(Debug, Serialize)]
+pub(crate) struct ExportedReco<'a> {
+    pub id: &'a str,
+    #[serde(rename = "type")]
+    pub typ: Option<&'a str>,
+    pub title: &'a str,
+    pub chapter: &'a str,
+    pub path: String,
+    pub anchor: String,
+    pub body: &'a str,
+}
+
+pub(crate) fn records(checklist: &Checklist) -> Vec<ExportedReco<'_>> {
+    checklist
+        .chapters()
+        .flat_map(|(chap_name, chap_path, entries)| {
+            entries.iter().map(move |entry| ExportedReco {
+                id: &entry.name,
+                typ: entry.typ.as_deref(),
+                title: &entry.title,
+                chapter: chap_name,
+                path: chap_path.to_string_lossy().into_owned(),
+                anchor: format!(
+                    "{}#{}",
+                    chap_path.with_extension("html").to_string_lossy(),
+                    entry.name
+                ),
+                body: &entry.body,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn write_records<W: std::io::Write>(
+    format: Format,
+    records: &[ExportedReco],
+    writer: W,
+) -> Result<(), Error> {
+    match format {
+        Format::Json => serde_json::to_writer_pretty(writer, records)?,
+        Format::Yaml => serde_yaml_ng::to_writer(writer, records)?,
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Markdown => write_table(records, writer)?,
+    }
+    Ok(())
+}
+
+/// Writes the records as a Markdown table, linking to the chapters.
+fn write_table<W: std::io::Write>(records: &[ExportedReco], mut writer: W) -> std::io::Result<()> {
+    writeln!(writer, "| Id | Type | Title | Chapter |")?;
+    writeln!(writer, "|----|------|-------|---------|")?;
+    for record in records {
+        writeln!(
+            writer,
+            "| {} | {} | {} | [{}]({}#{}) |",
+            record.id,
+            cell(record.typ.unwrap_or_default()),
+            cell(record.title),
+            cell(record.chapter),
+            record.path,
+            record.id
+        )?;
+    }
+    Ok(())
+}
+
+/// Directory of the generated files: `dir` relative to the book root, or next
+/// to the build directory as default (e.g. `book/en-checklist` for `book/en`),
+/// since renderers empty their destination when writing the book.
+pub(crate) fn output_dir(dir: Option<&Path>, root: &Path, build_dir: &Path) -> PathBuf {
+    match dir {
+        Some(dir) => root.join(dir),
+        None => {
+            let build_dir = root.join(build_dir);
+            let mut name = build_dir.file_name().unwrap_or_default().to_os_string();
+            name.push("-checklist");
+            build_dir.with_file_name(name)
+        }
+    }
+}
+
+/// Writes the collected recommendations in every configured format.
+pub(crate) fn export(
+    config: &Config,
+    checklist: &Checklist,
+    root: &Path,
+    build_dir: &Path,
+) -> Result<(), Error> {
+    let dir = output_dir(config.dir.as_deref(), root, build_dir);
+    fs::create_dir_all(&dir)?;
+    let stem = config.file_stem.as_deref().unwrap_or("checklist");
+    let records = records(checklist);
+    for format in &config.formats {
+        let path = dir.join(format!("{stem}.{}", format.extension()));
+        let file = File::create(&path)?;
+        write_records(*format, &records, file)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+
+    fn checklist() -> Checklist {
+        let mut checklist = Checklist::new();
+        checklist.insert(
+            "Memory",
+            &PathBuf::from("unsafe/memory.md"),
+            CheckEntry {
+                body: "The function MUST NOT be used, really.".to_string(),
+                line: 54,
+                ..CheckEntry::new("MEM-FORGET", Some("Rule"), "Do not use `mem::forget`")
+            },
+        );
+        checklist
+    }
+
+    #[test]
+    fn json() {
+        let checklist = checklist();
+        let mut out = Vec::new();
+        write_records(Format::Json, &records(&checklist), &mut out).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            serde_json::json!([{
+                "id": "MEM-FORGET",
+                "type": "Rule",
+                "title": "Do not use `mem::forget`",
+                "chapter": "Memory",
+                "path": "unsafe/memory.md",
+                "anchor": "unsafe/memory.html#MEM-FORGET",
+                "body": "The function MUST NOT be used, really."
+            }]),
+            value
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let checklist = checklist();
+        let mut out = Vec::new();
+        write_records(Format::Csv, &records(&checklist), &mut out).unwrap();
+        assert_eq!(
+            "id,type,title,chapter,path,anchor,body\n\
+             MEM-FORGET,Rule,Do not use `mem::forget`,Memory,unsafe/memory.md,unsafe/memory.html#MEM-FORGET,\"The function MUST NOT be used, really.\"\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        let checklist = checklist();
+        let mut out = Vec::new();
+        write_records(Format::Markdown, &records(&checklist), &mut out).unwrap();
+        assert_eq!(
+            "| Id | Type | Title | Chapter |\n\
+             |----|------|-------|---------|\n\
+             | MEM-FORGET | Rule | Do not use `mem::forget` | [Memory](unsafe/memory.md#MEM-FORGET) |\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn default_dir() {
+        let root = Path::new("/book");
+        assert_eq!(
+            PathBuf::from("/book/book/en-checklist"),
+            output_dir(None, root, Path::new("book/en"))
+        );
+        assert_eq!(
+            PathBuf::from("/book/export"),
+            output_dir(Some(Path::new("export")), root, Path::new("book/en"))
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use crate::checklist::Checklist;
+use crate::checklist_pre::collect;
+use crate::export::{records, write_records, Format};
+use crate::summary::load_book;
+use crate::validation;
+use mdbook_preprocessor::errors::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Reads the `[preprocessor.checklist.validation]` table of a `book.toml`
+/// (default validation if missing).
+fn load_config(book_toml: &Path) -> Result<validation::Config, Error> {
+    let content = fs::read_to_string(book_toml)
+        .map_err(|e| Error::msg(format!("Cannot read {}: {e}", book_toml.display())))?;
+    let invalid = |e: toml::de::Error| Error::msg(format!("Invalid {}: {e}", book_toml.display()));
+    let book: toml::Value = toml::from_str(&content).map_err(invalid)?;
+    match book
+        .get("preprocessor")
+        .and_then(|table| table.get("checklist"))
+        .and_then(|table| table.get("validation"))
+    {
+        Some(table) => table.clone().try_into().map_err(invalid),
+        None => Ok(validation::Config::default()),
+    }
+}
+
+/// Prints the entries of the book in a source directory, and the violations
+/// of the validation rules (read from `book_toml` if any) on the error
+/// output. Returns whether there was no violation.
+pub(crate) fn run(src_dir: &Path, format: Format, book_toml: Option<&Path>) -> Result<bool, Error> {
+    let config = match book_toml {
+        Some(path) => load_config(path)?,
+        None => validation::Config::default(),
+    };
+    let mut book = load_book(src_dir)?;
+    let mut checklist = Checklist::new();
+    let mut violations = collect(&mut book, &mut checklist);
+    violations.extend(validation::check(&config, &checklist)?);
+    let mut output = Vec::new();
+    write_records(format, &records(&checklist), &mut output)?;
+    if let Format::Json = format {
+        output.push(b'\n');
+    }
+    print(&output, io::stdout().lock())?;
+    for violation in &violations {
+        eprintln!("{}: {violation}", src_dir.display());
+    }
+    Ok(violations.is_empty())
+}
+
+/// Writes the output, the reader stopping early (e.g. `| head`) not being an
+/// error.
+fn print<W: Write>(output: &[u8], mut writer: W) -> io::Result<()> {
+    match writer.write_all(output).and_then(|_| writer.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+    use std::path::PathBuf;
+
+    /// Writer whose reader is gone
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn broken_pipe() {
+        assert!(print(b"output", ClosedPipe).is_ok());
+        let mut output = Vec::new();
+        print(b"output", &mut output).unwrap();
+        assert_eq!(b"output", &output[..]);
+    }
+
+    #[test]
+    fn config() {
+        let mut checklist = Checklist::new();
+        checklist.insert(
+            "Chapter",
+            &PathBuf::from("chapter.md"),
+            CheckEntry::new("A", Some("Recommendation"), "Title"),
+        );
+        let dir = tempfile::TempDir::new().unwrap();
+        let book_toml = dir.path().join("book.toml");
+        fs::write(
+            &book_toml,
+            "[book]\ntitle = \"Book\"\n\n\
+             [preprocessor.checklist.validation]\ntypes = [\"Rule\"]\n",
+        )
+        .unwrap();
+        let config = load_config(&book_toml).unwrap();
+        assert_eq!(1, validation::check(&config, &checklist).unwrap().len());
+        fs::write(&book_toml, "[preprocessor.checklist]\n").unwrap();
+        let config = load_config(&book_toml).unwrap();
+        assert!(validation::check(&config, &checklist).unwrap().is_empty());
+    }
+}
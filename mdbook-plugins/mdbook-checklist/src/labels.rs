@@ -0,0 +1,86 @@
+use crate::checklist::{Checklist, TableConfig};
+use crate::checklist_pre::id_tags;
+use mdbook_preprocessor::book::{Book, BookItem};
+use std::cmp::Reverse;
+use std::path::Path;
+
+// Labels of the entries for the typesetter renderer, targets of their page
+// references: a paragraph before the `div` of the recommendations, and in place
+// of the anchor of the `{{#check}}` marks.
+
+/// Inserts the labels of the entries in every chapter of a book.
+pub(crate) fn insert(book: &mut Book, checklist: &Checklist, table: &TableConfig) {
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(chapter) = item else {
+            return;
+        };
+        let Some(path) = chapter.path.as_deref() else {
+            return;
+        };
+        chapter.content = label(&chapter.content, path, checklist, table);
+    });
+}
+
+/// Content of the chapter at `path` with the labels of its entries
+fn label(content: &str, path: &Path, checklist: &Checklist, table: &TableConfig) -> String {
+    let Some((_, _, entries)) = checklist.chapters().find(|(_, p, _)| *p == path) else {
+        return content.to_string();
+    };
+    let mut content = content.to_string();
+    for entry in entries.iter().filter(|entry| entry.typ.is_none()) {
+        let anchor = format!("<a id=\"{}\"></a>", entry.name);
+        content = content.replacen(&anchor, &table.label(&entry.name), 1);
+    }
+    let tags = id_tags(&content);
+    let mut starts: Vec<(usize, &str)> = entries
+        .iter()
+        .filter(|entry| entry.typ.is_some())
+        .filter_map(|entry| Some((tags.get(&entry.name)?.range.start, &entry.name as &str)))
+        .collect();
+    // from the last tag, for the offsets of the others to remain valid
+    starts.sort_by_key(|(start, _)| Reverse(*start));
+    for (start, id) in starts {
+        let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+        let prefix = &content[line_start..start];
+        if prefix.chars().all(|c| c.is_whitespace() || c == '>') {
+            // a paragraph of its own, in the same container as the tag
+            let blank = prefix.trim_end();
+            let paragraph = format!("{blank}\n{prefix}{}\n{blank}\n", table.label(id));
+            content.insert_str(line_start, &paragraph);
+        } else {
+            content.insert_str(start, &table.label(id));
+        }
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+
+    #[test]
+    fn labels() {
+        let checklist = Checklist::from_entries([
+            ("ffi.md", CheckEntry::new("FFI-NOPANIC", Some("Rule"), "")),
+            ("ffi.md", CheckEntry::new("FFI-CHECK", None, "")),
+            ("ffi.md", CheckEntry::new("FFI-QUOTED", Some("Rule"), "")),
+        ]);
+        let content = "Text.\n<div class=\"reco\" id=\"FFI-NOPANIC\">\n</div>\n\n\
+                       - <a id=\"FFI-CHECK\"></a>FFI-CHECK\n\n\
+                       > <div class=\"reco\" id=\"FFI-QUOTED\">\n> </div>\n";
+        assert_eq!(
+            "Text.\n\n`\\label{FFI-NOPANIC}`{=latex}\n\n\
+             <div class=\"reco\" id=\"FFI-NOPANIC\">\n</div>\n\n\
+             - `\\label{FFI-CHECK}`{=latex}FFI-CHECK\n\n\
+             >\n> `\\label{FFI-QUOTED}`{=latex}\n>\n\
+             > <div class=\"reco\" id=\"FFI-QUOTED\">\n> </div>\n",
+            label(
+                content,
+                Path::new("ffi.md"),
+                &checklist,
+                &TableConfig::default()
+            )
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use crate::checklist::Checklist;
+use crate::export::output_dir;
+use mdbook_preprocessor::errors::Error;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Output directory, relative to the book root (see [`output_dir`])
+    dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Allow,
+    Warn,
+    Deny,
+    Forbid,
+}
+
+impl Level {
+    fn parse(level: &str) -> Option<Self> {
+        match level {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            "forbid" => Some(Level::Forbid),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+            Level::Forbid => "forbid",
+        }
+    }
+}
+
+/// Lint groups, overridden by the individual lints of the same table
+const GROUPS: &[&str] = &[
+    // rustc
+    "warnings",
+    "future_incompatible",
+    "let_underscore",
+    "nonstandard_style",
+    "rust_2018_compatibility",
+    "rust_2018_idioms",
+    "rust_2021_compatibility",
+    "rust_2024_compatibility",
+    "unused",
+    // clippy
+    "all",
+    "cargo",
+    "complexity",
+    "correctness",
+    "nursery",
+    "pedantic",
+    "perf",
+    "restriction",
+    "style",
+    "suspicious",
+];
+
+/// Settings of a lint, with the ids of the recommendations it enforces
+struct Lint<'a> {
+    level: Level,
+    ids: Vec<&'a str>,
+}
+
+/// Parses `[tool::]lint[=level]` into a lint table, lint name and level
+/// ("deny" as default).
+fn parse_lint(lint: &str) -> Result<(&'static str, &str, Level), String> {
+    let (lint, level) = match lint.split_once('=') {
+        Some((lint, level)) => {
+            let level = level.trim();
+            let level =
+                Level::parse(level).ok_or_else(|| format!("unknown lint level \"{level}\""))?;
+            (lint.trim(), level)
+        }
+        None => (lint, Level::Deny),
+    };
+    let (table, name) = match lint.split_once("::") {
+        None => ("rust", lint),
+        Some(("clippy", name)) => ("clippy", name),
+        Some(("rustdoc", name)) => ("rustdoc", name),
+        Some((tool, _)) => return Err(format!("unknown lint tool \"{tool}\"")),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("invalid lint name \"{lint}\""));
+    }
+    Ok((table, name, level))
+}
+
+/// Comment line listing recommendation ids
+fn comment(ids: &[&str]) -> String {
+    format!("# {}\n", ids.join(", "))
+}
+
+/// Generates the `[lints]` tables of `Cargo.toml` and the content of
+/// `clippy.toml` from the lints of the entries. When several entries set the
+/// same lint, the strictest level applies.
+pub(crate) fn generate(checklist: &Checklist) -> Result<(String, String), Error> {
+    let mut tables: BTreeMap<&str, BTreeMap<&str, Lint>> = BTreeMap::new();
+    let mut settings: BTreeMap<String, (String, Vec<&str>)> = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (_, _, entries) in checklist.chapters() {
+        for entry in entries {
+            for lint in &entry.meta.lints {
+                let (table, name, level) = match parse_lint(lint) {
+                    Ok(lint) => lint,
+                    Err(message) => {
+                        errors.push(format!("{}: {message}", entry.name));
+                        continue;
+                    }
+                };
+                let lint = tables
+                    .entry(table)
+                    .or_default()
+                    .entry(name)
+                    .or_insert(Lint {
+                        level,
+                        ids: Vec::new(),
+                    });
+                lint.level = lint.level.max(level);
+                lint.ids.push(&entry.name);
+            }
+            for setting in &entry.meta.clippy_config {
+                match toml::from_str::<toml::value::Table>(setting) {
+                    Ok(table) if table.len() == 1 => {
+                        let (key, value) = table.into_iter().next().unwrap();
+                        let (previous, ids) = settings
+                            .entry(key.clone())
+                            .or_insert_with(|| (value.to_string(), Vec::new()));
+                        if *previous != value.to_string() {
+                            errors.push(format!(
+                                "{}: clippy setting \"{key}\" already set to {previous} by {}",
+                                entry.name,
+                                ids.join(", ")
+                            ));
+                        }
+                        ids.push(&entry.name);
+                    }
+                    _ => errors.push(format!(
+                        "{}: invalid clippy setting \"{setting}\"",
+                        entry.name
+                    )),
+                }
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::msg(format!(
+            "Invalid lints in recommendations:\n  {}",
+            errors.join("\n  ")
+        )));
+    }
+
+    let mut cargo = String::new();
+    for (table, lints) in &tables {
+        if !cargo.is_empty() {
+            cargo.push('\n');
+        }
+        cargo.push_str(&format!("[lints.{table}]\n"));
+        for (name, lint) in lints {
+            cargo.push_str(&comment(&lint.ids));
+            if GROUPS.contains(name) {
+                cargo.push_str(&format!(
+                    "{name} = {{ level = \"{}\", priority = -1 }}\n",
+                    lint.level.as_str()
+                ));
+            } else {
+                cargo.push_str(&format!("{name} = \"{}\"\n", lint.level.as_str()));
+            }
+        }
+    }
+    let mut clippy = String::new();
+    for (key, (value, ids)) in &settings {
+        clippy.push_str(&comment(ids));
+        clippy.push_str(&format!("{key} = {value}\n"));
+    }
+    Ok((cargo, clippy))
+}
+
+/// Writes the lint tables in `lints.toml` (to be pasted in `Cargo.toml`) and
+/// the clippy settings in `clippy.toml`.
+pub(crate) fn write(
+    config: &Config,
+    checklist: &Checklist,
+    root: &Path,
+    build_dir: &Path,
+) -> Result<(), Error> {
+    let (cargo, clippy) = generate(checklist)?;
+    let dir = output_dir(config.dir.as_deref(), root, build_dir);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("lints.toml"), cargo)?;
+    fs::write(dir.join("clippy.toml"), clippy)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::{CheckEntry, Metadata};
+
+    fn checklist(entries: &[(&str, &[&str], &[&str])]) -> Checklist {
+        let to_vec = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Checklist::from_entries(entries.iter().map(|(id, lints, clippy_config)| {
+            (
+                "chapter.md",
+                CheckEntry {
+                    meta: Metadata {
+                        lints: to_vec(lints),
+                        clippy_config: to_vec(clippy_config),
+                        ..Default::default()
+                    },
+                    ..CheckEntry::new(id, Some("Rule"), "")
+                },
+            )
+        }))
+    }
+
+    #[test]
+    fn tables() {
+        let checklist = checklist(&[
+            ("DENV-LINTER", &["clippy::all=warn"], &[]),
+            ("LANG-UNSAFE", &["unsafe_code=forbid"], &[]),
+            (
+                "LANG-LIMIT-PANIC-SRC",
+                &["clippy::unwrap_used=warn", "clippy::panic=warn"],
+                &["allow-unwrap-in-tests = true"],
+            ),
+            ("MEM-FORGET-LINT", &["clippy::mem_forget"], &[]),
+            (
+                "LANG-NOPANIC",
+                &["clippy::panic"],
+                &["allow-unwrap-in-tests=true"],
+            ),
+        ]);
+        let (cargo, clippy) = generate(&checklist).unwrap();
+        assert_eq!(
+            r#"[lints.clippy]
+# DENV-LINTER
+all = { level = "warn", priority = -1 }
+# MEM-FORGET-LINT
+mem_forget = "deny"
+# LANG-LIMIT-PANIC-SRC, LANG-NOPANIC
+panic = "deny"
+# LANG-LIMIT-PANIC-SRC
+unwrap_used = "warn"
+
+[lints.rust]
+# LANG-UNSAFE
+unsafe_code = "forbid"
+"#,
+            cargo
+        );
+        assert_eq!(
+            "# LANG-LIMIT-PANIC-SRC, LANG-NOPANIC\nallow-unwrap-in-tests = true\n",
+            clippy
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let checklist = checklist(&[
+            ("A", &["clippy::panic=error", "miri::x", "clippy::"], &["x"]),
+            ("B", &[], &["max-fn-params-bools = 1"]),
+            ("C", &[], &["max-fn-params-bools = 2"]),
+        ]);
+        assert_eq!(
+            "Invalid lints in recommendations:\n  \
+             A: unknown lint level \"error\"\n  \
+             A: unknown lint tool \"miri\"\n  \
+             A: invalid lint name \"clippy::\"\n  \
+             A: invalid clippy setting \"x\"\n  \
+             C: clippy setting \"max-fn-params-bools\" already set to 1 by B",
+            generate(&checklist).unwrap_err().to_string()
+        );
+    }
+}
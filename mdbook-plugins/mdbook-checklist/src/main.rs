@@ -1,7 +1,21 @@
 #![forbid(unsafe_code)]
 
+mod audit;
+mod changelog;
+mod chapter_list;
 mod checklist;
 mod checklist_pre;
+mod crossref;
+mod export;
+mod extract;
+mod labels;
+mod lints;
+mod numbering;
+mod parity;
+mod placement;
+mod relations;
+mod summary;
+mod validation;
 
 use checklist_pre::ChecklistPre;
 
@@ -9,6 +23,7 @@ use mdbook_preprocessor::errors::Error;
 use mdbook_preprocessor::Preprocessor;
 
 use std::io;
+use std::path::{Path, PathBuf};
 use std::process;
 
 use clap::{Parser, Subcommand};
@@ -27,17 +42,75 @@ enum Commands {
         /// Renderer name
         renderer: String,
     },
+    /// Print the recommendations of a book, read from its sources
+    Extract {
+        /// Source directory of the book (containing `SUMMARY.md`)
+        src_dir: PathBuf,
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "json")]
+        format: export::Format,
+        /// `book.toml` providing the validation rules
+        #[arg(short, long, value_name = "BOOK_TOML")]
+        config: Option<PathBuf>,
+    },
+    /// Compare the recommendations of two translations of a book
+    Parity {
+        /// Source directory of the first book (containing `SUMMARY.md`)
+        left: PathBuf,
+        /// Source directory of the second book
+        right: PathBuf,
+        /// Translation of a type from the first book to the second one
+        #[arg(short, long = "type", value_name = "LEFT=RIGHT", value_parser = parse_type_pair)]
+        types: Vec<(String, String)>,
+    },
+}
+
+fn parse_type_pair(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(left, right)| (left.to_string(), right.to_string()))
+        .ok_or_else(|| format!("invalid type translation \"{s}\", expected LEFT=RIGHT"))
 }
 
 fn main() {
     let cli = Cli::parse();
     let preprocessor = ChecklistPre;
 
-    if let Some(Commands::Supports { renderer }) = &cli.command {
-        handle_supports(&preprocessor, renderer);
-    } else if let Err(e) = handle_preprocessing(&preprocessor) {
-        eprintln!("{e:?}");
-        process::exit(1);
+    match &cli.command {
+        Some(Commands::Supports { renderer }) => handle_supports(&preprocessor, renderer),
+        Some(Commands::Extract {
+            src_dir,
+            format,
+            config,
+        }) => handle_extract(src_dir, *format, config.as_deref()),
+        Some(Commands::Parity { left, right, types }) => handle_parity(left, right, types),
+        None => {
+            if let Err(e) = handle_preprocessing(&preprocessor) {
+                eprintln!("{e:?}");
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn handle_extract(src_dir: &Path, format: export::Format, config: Option<&Path>) -> ! {
+    match extract::run(src_dir, format, config) {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("{e:?}");
+            process::exit(2);
+        }
+    }
+}
+
+fn handle_parity(left: &Path, right: &Path, types: &[(String, String)]) -> ! {
+    match parity::run(left, right, types) {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("{e:?}");
+            process::exit(2);
+        }
     }
 }
 
@@ -0,0 +1,182 @@
+use crate::checklist::Checklist;
+use crate::checklist_pre::{id_tags, IdTag};
+use mdbook_preprocessor::book::{Book, BookItem};
+use mdbook_preprocessor::errors::Error;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Format of the numbers, `{n}` being replaced by the number ("R{n}" as
+    /// default)
+    format: Option<String>,
+    /// File keeping the numbers of the recommendations, relative to the book
+    /// root
+    lock_file: Option<PathBuf>,
+}
+
+/// Numbers of the recommendations by id
+type Lock = BTreeMap<String, u32>;
+
+const LOCK_HEADER: &str = "# Numbers of the recommendations, generated by mdbook-checklist.\n\
+                           # Numbers are never reused: remove an entry to renumber it.\n\n";
+
+/// Numbers the recommendations in book order, keeping the numbers of the lock
+/// file if any (new recommendations being numbered after the last one). The
+/// lock file is updated when new recommendations are numbered. Returns the
+/// formatted numbers by id.
+pub(crate) fn assign(
+    config: &Config,
+    checklist: &Checklist,
+    root: &Path,
+) -> Result<HashMap<String, String>, Error> {
+    let lock_path = config.lock_file.as_ref().map(|path| root.join(path));
+    let mut lock: Lock = match &lock_path {
+        Some(path) if path.exists() => {
+            let content = fs::read_to_string(path)?;
+            toml::from_str(&content)
+                .map_err(|e| Error::msg(format!("Invalid lock file {}: {e}", path.display())))?
+        }
+        _ => Lock::new(),
+    };
+    let updated = number(&mut lock, checklist);
+    if let (Some(path), true) = (&lock_path, updated) {
+        fs::write(path, format!("{LOCK_HEADER}{}", toml::to_string(&lock)?))?;
+    }
+    let format = config.format.as_deref().unwrap_or("R{n}");
+    Ok(lock
+        .into_iter()
+        .map(|(id, n)| (id, format.replace("{n}", &n.to_string())))
+        .collect())
+}
+
+/// Numbers the recommendations missing from the lock, returning whether there
+/// was any.
+fn number(lock: &mut Lock, checklist: &Checklist) -> bool {
+    let mut next = lock.values().max().map_or(1, |n| n + 1);
+    let mut updated = false;
+    for (_, _, entries) in checklist.chapters() {
+        for entry in entries.iter().filter(|entry| entry.typ.is_some()) {
+            if !lock.contains_key(&entry.name) {
+                lock.insert(entry.name.clone(), next);
+                next += 1;
+                updated = true;
+            }
+        }
+    }
+    updated
+}
+
+/// Adds a `number` attribute to the `div` of the numbered recommendations, to
+/// be shown by the stylesheet.
+pub(crate) fn inject(book: &mut Book, checklist: &Checklist, numbers: &HashMap<String, String>) {
+    let by_path: HashMap<&Path, Vec<&str>> = checklist
+        .chapters()
+        .map(|(_, path, entries)| (path, entries.iter().map(|e| &e.name as &str).collect()))
+        .collect();
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(chapter) = item else {
+            return;
+        };
+        let Some(ids) = chapter.path.as_deref().and_then(|path| by_path.get(path)) else {
+            return;
+        };
+        let tags = id_tags(&chapter.content);
+        let mut numbered: Vec<(&IdTag, &String)> = ids
+            .iter()
+            .filter_map(|id| Some((tags.get(*id)?, numbers.get(*id)?)))
+            .collect();
+        // from the last tag, for the offsets of the others to remain valid
+        numbered.sort_by_key(|(tag, _)| Reverse(tag.range.start));
+        for (tag, number) in numbered {
+            chapter
+                .content
+                .insert_str(tag.id_end, &format!(" number=\"{number}\""));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+    use mdbook_preprocessor::book::Chapter;
+
+    fn checklist(ids: &[&str]) -> Checklist {
+        Checklist::from_entries(ids.iter().map(|id| {
+            let typ = (!id.starts_with("CHECK")).then_some("Rule");
+            ("chapter.md", CheckEntry::new(id, typ, ""))
+        }))
+    }
+
+    #[test]
+    fn stable_numbers() {
+        let mut lock = Lock::new();
+        assert!(number(&mut lock, &checklist(&["A", "CHECK-1", "B", "C"])));
+        assert_eq!(
+            vec![("A", 1), ("B", 2), ("C", 3)],
+            lock.iter()
+                .map(|(id, n)| (id as &str, *n))
+                .collect::<Vec<_>>()
+        );
+        // removed recommendations keep their number, never reused
+        assert!(number(&mut lock, &checklist(&["A", "NEW", "B"])));
+        assert_eq!(Some(&3), lock.get("C"));
+        assert_eq!(Some(&4), lock.get("NEW"));
+        assert!(!number(&mut lock, &checklist(&["A", "NEW", "B"])));
+    }
+
+    #[test]
+    fn quoted_attributes() {
+        let checklist = checklist(&["A", "B"]);
+        let content = "<div class=\"reco\" title=\"a > b, id=&quot;B&quot;\" id=\"A\">\n</div>\n\n\
+                       <div class='reco' id='B'>\n</div>\n\n\
+                       `<div id=\"A\">`\n";
+        let mut book = Book::new_with_items(vec![BookItem::Chapter(Chapter::new(
+            "Chapter",
+            content.to_string(),
+            "chapter.md",
+            vec![],
+        ))]);
+        let numbers = HashMap::from([
+            ("A".to_string(), "R1".to_string()),
+            ("B".to_string(), "R2".to_string()),
+        ]);
+        inject(&mut book, &checklist, &numbers);
+        assert_eq!(
+            "<div class=\"reco\" title=\"a > b, id=&quot;B&quot;\" id=\"A\" number=\"R1\">\n</div>\n\n\
+             <div class='reco' id='B' number=\"R2\">\n</div>\n\n\
+             `<div id=\"A\">`\n",
+            book.chapters().next().unwrap().content
+        );
+    }
+
+    #[test]
+    fn injected() {
+        let checklist = checklist(&["A-1", "A"]);
+        let content = "<div class=\"reco\" id=\"A-1\" type=\"Rule\">\n</div>\n\n\
+                       <div data-id=\"A\">\n</div>\n\n\
+                       <div id=\"A\" class=\"reco\">\n</div>\n";
+        let mut book = Book::new_with_items(vec![BookItem::Chapter(Chapter::new(
+            "Chapter",
+            content.to_string(),
+            "chapter.md",
+            vec![],
+        ))]);
+        let numbers = HashMap::from([
+            ("A-1".to_string(), "R1".to_string()),
+            ("A".to_string(), "R2".to_string()),
+        ]);
+        inject(&mut book, &checklist, &numbers);
+        assert_eq!(
+            "<div class=\"reco\" id=\"A-1\" number=\"R1\" type=\"Rule\">\n</div>\n\n\
+             <div data-id=\"A\">\n</div>\n\n\
+             <div id=\"A\" number=\"R2\" class=\"reco\">\n</div>\n",
+            book.chapters().next().unwrap().content
+        );
+    }
+}
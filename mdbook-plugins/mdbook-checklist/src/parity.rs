@@ -0,0 +1,144 @@
+use crate::checklist::Checklist;
+use crate::checklist_pre::collect;
+use crate::summary::load_book;
+use crate::validation;
+use mdbook_preprocessor::errors::Error;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Collects the entries of the book in a source directory, failing on
+/// malformed entries.
+fn load_checklist(src_dir: &Path) -> Result<Checklist, Error> {
+    let mut book = load_book(src_dir)?;
+    let mut checklist = Checklist::new();
+    validation::into_result(collect(&mut book, &mut checklist))
+        .map_err(|e| Error::msg(format!("{}: {e}", src_dir.display())))?;
+    Ok(checklist)
+}
+
+/// Entries of a checklist by id: chapter path and type
+fn index(checklist: &Checklist) -> Vec<(&str, &Path, &str)> {
+    checklist
+        .chapters()
+        .flat_map(|(_, path, entries)| {
+            entries.iter().map(move |entry| {
+                let typ = entry.typ.as_deref().unwrap_or_default();
+                (&entry.name as &str, path, typ)
+            })
+        })
+        .collect()
+}
+
+/// Compares the entries of two books, `types` mapping the types of the left
+/// book to the types of the right one. Types missing from the map are
+/// expected to be identical.
+pub(crate) fn compare(
+    (left_name, left): (&str, &Checklist),
+    (right_name, right): (&str, &Checklist),
+    types: &HashMap<String, String>,
+) -> Vec<String> {
+    let left = index(left);
+    let right = index(right);
+    let right_by_id: HashMap<&str, (&Path, &str)> = right
+        .iter()
+        .map(|(id, path, typ)| (*id, (*path, *typ)))
+        .collect();
+    let mut differences = Vec::new();
+    for (id, left_path, left_type) in &left {
+        match right_by_id.get(id) {
+            None => differences.push(format!(
+                "{id}: only in {left_name} ({})",
+                left_path.display()
+            )),
+            Some((right_path, right_type)) => {
+                let expected = types
+                    .get(*left_type)
+                    .map(|t| t as &str)
+                    .unwrap_or(left_type);
+                if expected != *right_type {
+                    differences.push(format!(
+                        "{id}: type \"{left_type}\" in {left_name} but \"{right_type}\" in {right_name} (expected \"{expected}\")"
+                    ));
+                }
+                if left_path != right_path {
+                    differences.push(format!(
+                        "{id}: in {} in {left_name} but in {} in {right_name}",
+                        left_path.display(),
+                        right_path.display()
+                    ));
+                }
+            }
+        }
+    }
+    for (id, right_path, _) in &right {
+        if !left.iter().any(|(left_id, _, _)| left_id == id) {
+            differences.push(format!(
+                "{id}: only in {right_name} ({})",
+                right_path.display()
+            ));
+        }
+    }
+    differences
+}
+
+/// Prints the differences between the entries of two books and returns
+/// whether they are in parity.
+pub(crate) fn run(left: &Path, right: &Path, types: &[(String, String)]) -> Result<bool, Error> {
+    let left_checklist = load_checklist(left)?;
+    let right_checklist = load_checklist(right)?;
+    let types = types.iter().cloned().collect();
+    let left_name = left.display().to_string();
+    let right_name = right.display().to_string();
+    let differences = compare(
+        (&left_name, &left_checklist),
+        (&right_name, &right_checklist),
+        &types,
+    );
+    for difference in &differences {
+        println!("{difference}");
+    }
+    Ok(differences.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+
+    fn checklist(entries: &[(&str, &str, &str)]) -> Checklist {
+        Checklist::from_entries(
+            entries
+                .iter()
+                .map(|(path, id, typ)| (*path, CheckEntry::new(id, Some(typ), ""))),
+        )
+    }
+
+    #[test]
+    fn differences() {
+        let en = checklist(&[
+            ("devenv.md", "DENV-STABLE", "Rule"),
+            ("devenv.md", "DENV-FORMAT", "Recommendation"),
+            ("standard.md", "MEM-MUT-REC-RC", "Rule"),
+            ("errors.md", "LANG-ERRWRAP", "Recommendation"),
+        ]);
+        let fr = checklist(&[
+            ("devenv.md", "DENV-STABLE", "Règle"),
+            ("devenv.md", "DENV-FORMAT", "Règle"),
+            ("unsafe/memory.md", "MEM-MUT-REC-RC", "Règle"),
+            ("errors.md", "LANG-ERR-WRAP", "Recommandation"),
+        ]);
+        let types = HashMap::from([
+            ("Rule".to_string(), "Règle".to_string()),
+            ("Recommendation".to_string(), "Recommandation".to_string()),
+        ]);
+        assert_eq!(
+            vec![
+                "DENV-FORMAT: type \"Recommendation\" in en but \"Règle\" in fr (expected \"Recommandation\")",
+                "MEM-MUT-REC-RC: in standard.md in en but in unsafe/memory.md in fr",
+                "LANG-ERRWRAP: only in en (errors.md)",
+                "LANG-ERR-WRAP: only in fr (errors.md)",
+            ],
+            compare(("en", &en), ("fr", &fr), &types)
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::checklist::Checklist;
+use mdbook_preprocessor::book::{Book, BookItem, Chapter, SectionNumber};
+use mdbook_preprocessor::errors::Error;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Where the checklist chapter is placed in the book (at the end as default).
+/// At most one option can be given.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Replaces the content of the chapter with this path in `SUMMARY.md`
+    placeholder: Option<PathBuf>,
+    /// Appended as a numbered chapter at the end of the part with this title
+    part: Option<String>,
+    /// Appended as a sub-chapter of the chapter with this path
+    parent: Option<PathBuf>,
+}
+
+enum Placement<'a> {
+    End,
+    Placeholder(&'a Path),
+    Part(&'a str),
+    Parent(&'a Path),
+}
+
+impl Config {
+    fn placement(&self) -> Result<Placement<'_>, Error> {
+        match (&self.placeholder, &self.part, &self.parent) {
+            (None, None, None) => Ok(Placement::End),
+            (Some(path), None, None) => Ok(Placement::Placeholder(path)),
+            (None, Some(title), None) => Ok(Placement::Part(title)),
+            (None, None, Some(path)) => Ok(Placement::Parent(path)),
+            _ => Err(Error::msg(
+                "Only one of `placeholder`, `part` and `parent` can be used to place the checklist",
+            )),
+        }
+    }
+}
+
+const DEFAULT_PATH: &str = "checklist.md";
+
+fn find_chapter<'a>(items: &'a mut [BookItem], path: &Path) -> Option<&'a mut Chapter> {
+    for item in items {
+        if let BookItem::Chapter(chapter) = item {
+            if chapter.path.as_deref() == Some(path) {
+                return Some(chapter);
+            }
+            if let Some(chapter) = find_chapter(&mut chapter.sub_items, path) {
+                return Some(chapter);
+            }
+        }
+    }
+    None
+}
+
+/// Generates the checklist chapter and inserts it in the book, renumbering the
+/// sections when needed (before generating the chapter, which lists them).
+pub(crate) fn insert(
+    book: &mut Book,
+    config: &Config,
+    mut checklist: Checklist,
+) -> Result<(), Error> {
+    let placement = config.placement()?;
+    let path = match placement {
+        Placement::Placeholder(path) => path,
+        _ => Path::new(DEFAULT_PATH),
+    };
+    // chapter to be replaced by the checklist once the book is renumbered
+    let slot = |number, parent_names| Chapter {
+        number,
+        parent_names,
+        ..Default::default()
+    };
+    match placement {
+        Placement::End => {
+            let checklist = checklist.generate_chapter(path);
+            book.items.push(BookItem::Chapter(checklist));
+        }
+        Placement::Placeholder(path) => {
+            let placeholder = find_chapter(&mut book.items, path).ok_or_else(|| {
+                Error::msg(format!(
+                    "Cannot find the checklist placeholder chapter {}",
+                    path.display()
+                ))
+            })?;
+            placeholder.content = checklist.generate_chapter(path).content;
+        }
+        Placement::Part(title) => {
+            let start = book
+                .items
+                .iter()
+                .position(|item| matches!(item, BookItem::PartTitle(t) if t == title))
+                .ok_or_else(|| Error::msg(format!("Cannot find the part \"{title}\"")))?;
+            // end of the part: next part title, separator or unnumbered chapter
+            let end = book.items[start + 1..]
+                .iter()
+                .position(|item| match item {
+                    BookItem::Chapter(chapter) => chapter.number.is_none(),
+                    BookItem::Separator | BookItem::PartTitle(_) => true,
+                })
+                .map(|i| start + 1 + i)
+                .unwrap_or(book.items.len());
+            let chapter = slot(Some(SectionNumber::default()), vec![]);
+            book.items.insert(end, BookItem::Chapter(chapter));
+            renumber(&mut book.items, &[]);
+            checklist.renumber(book);
+            if let BookItem::Chapter(chapter) = &mut book.items[end] {
+                fill(chapter, checklist.generate_chapter(path));
+            }
+        }
+        Placement::Parent(parent_path) => {
+            let parent = find_chapter(&mut book.items, parent_path).ok_or_else(|| {
+                Error::msg(format!(
+                    "Cannot find the checklist parent chapter {}",
+                    parent_path.display()
+                ))
+            })?;
+            let mut parent_names = parent.parent_names.clone();
+            parent_names.push(parent.name.clone());
+            let number = parent.number.as_ref().map(|_| SectionNumber::default());
+            parent
+                .sub_items
+                .push(BookItem::Chapter(slot(number, parent_names)));
+            renumber(&mut book.items, &[]);
+            checklist.renumber(book);
+            let parent = find_chapter(&mut book.items, parent_path).expect("parent chapter");
+            if let Some(BookItem::Chapter(chapter)) = parent.sub_items.last_mut() {
+                fill(chapter, checklist.generate_chapter(path));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces a chapter inserted in the book by the checklist chapter, keeping
+/// its number and parents.
+fn fill(slot: &mut Chapter, mut checklist: Chapter) {
+    checklist.number = slot.number.take();
+    checklist.parent_names = std::mem::take(&mut slot.parent_names);
+    *slot = checklist;
+}
+
+/// Numbers sequentially the numbered chapters, following their nesting.
+fn renumber(items: &mut [BookItem], prefix: &[u32]) {
+    let mut counter = 0;
+    for item in items {
+        if let BookItem::Chapter(chapter) = item {
+            if chapter.number.is_some() {
+                counter += 1;
+                let mut number = prefix.to_vec();
+                number.push(counter);
+                renumber(&mut chapter.sub_items, &number);
+                chapter.number = Some(SectionNumber::new(number));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::{CheckEntry, Location};
+
+    fn chapter(name: &str, number: Option<Vec<u32>>, sub_items: Vec<BookItem>) -> BookItem {
+        let mut chapter = Chapter::new(name, String::new(), format!("{name}.md"), vec![]);
+        chapter.number = number.map(SectionNumber::new);
+        chapter.sub_items = sub_items;
+        BookItem::Chapter(chapter)
+    }
+
+    fn book() -> Book {
+        Book::new_with_items(vec![
+            chapter("introduction", None, vec![]),
+            BookItem::PartTitle("Lifecycle".to_string()),
+            chapter("devenv", Some(vec![1]), vec![]),
+            chapter("libraries", Some(vec![2]), vec![]),
+            BookItem::PartTitle("Language".to_string()),
+            chapter(
+                "unsafe",
+                Some(vec![3]),
+                vec![chapter("unsafe/ffi", Some(vec![3, 1]), vec![])],
+            ),
+            chapter("LICENSE", None, vec![]),
+        ])
+    }
+
+    fn checklist() -> Checklist {
+        let mut checklist = Checklist::new();
+        checklist.set_title("Checklist".to_string());
+        checklist
+    }
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn numbered(book: &Book) -> Vec<String> {
+        book.chapters().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn part() {
+        let mut book = book();
+        insert(&mut book, &config("part = \"Lifecycle\""), checklist()).unwrap();
+        assert_eq!(
+            vec![
+                "introduction",
+                "1. devenv",
+                "2. libraries",
+                "3. Checklist",
+                "4. unsafe",
+                "4.1. unsafe/ffi",
+                "LICENSE"
+            ],
+            numbered(&book)
+        );
+        assert!(matches!(&book.items[4], BookItem::Chapter(c) if c.name == "Checklist"));
+    }
+
+    #[test]
+    fn parent() {
+        let mut book = book();
+        insert(&mut book, &config("parent = \"unsafe.md\""), checklist()).unwrap();
+        assert_eq!(
+            vec![
+                "introduction",
+                "1. devenv",
+                "2. libraries",
+                "3. unsafe",
+                "3.1. unsafe/ffi",
+                "3.2. Checklist",
+                "LICENSE"
+            ],
+            numbered(&book)
+        );
+        let checklist = book.chapters().find(|c| c.name == "Checklist").unwrap();
+        assert_eq!(vec!["unsafe".to_string()], checklist.parent_names);
+
+        let config = config("parent = \"introduction.md\"");
+        insert(&mut book, &config, self::checklist()).unwrap();
+        let checklist = book.chapters().nth(1).unwrap();
+        assert_eq!("Checklist", checklist.to_string());
+    }
+
+    /// Checklist with an entry in `unsafe/ffi.md`
+    fn located() -> Checklist {
+        let mut checklist = checklist();
+        let path = PathBuf::from("unsafe/ffi.md");
+        checklist.insert(
+            "unsafe/ffi",
+            &path,
+            CheckEntry::new("FFI-1", Some("Rule"), "Title"),
+        );
+        let location = Location {
+            part: Some("Language".to_string()),
+            parents: vec!["unsafe".to_string()],
+            number: Some(SectionNumber::new(vec![3, 1])),
+        };
+        checklist.locate(&path, location);
+        checklist
+    }
+
+    #[test]
+    fn renumbered_list() {
+        let mut book = book();
+        insert(&mut book, &config("part = \"Lifecycle\""), located()).unwrap();
+        let checklist = book.chapters().find(|c| c.name == "Checklist").unwrap();
+        assert!(checklist
+            .content
+            .contains("\n - 4. unsafe\n\n   - 4.1. unsafe/ffi:\n"));
+
+        let mut book = self::book();
+        insert(&mut book, &config("parent = \"devenv.md\""), located()).unwrap();
+        let checklist = book.chapters().find(|c| c.name == "Checklist").unwrap();
+        assert_eq!("1.1. Checklist", checklist.to_string());
+        assert_eq!(vec!["devenv".to_string()], checklist.parent_names);
+        assert!(checklist
+            .content
+            .contains("\n - 3. unsafe\n\n   - 3.1. unsafe/ffi:\n"));
+    }
+
+    #[test]
+    fn placeholder() {
+        let mut book = book();
+        let config = config("placeholder = \"unsafe/ffi.md\"");
+        insert(&mut book, &config, checklist()).unwrap();
+        let placeholder = book.chapters().find(|c| c.name == "unsafe/ffi").unwrap();
+        assert!(placeholder.content.starts_with("# Checklist\n"));
+        assert_eq!("3.1. unsafe/ffi", placeholder.to_string());
+
+        let config = self::config("placeholder = \"missing.md\"");
+        assert!(insert(&mut book, &config, checklist()).is_err());
+        let config = self::config("part = \"Lifecycle\"\nparent = \"unsafe.md\"");
+        assert!(insert(&mut book, &config, checklist()).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::checklist::{CheckEntry, Checklist};
+use crate::checklist_pre::{id_tags, DIV_TAG_RE};
+use crate::crossref::href;
+use crate::export::output_dir;
+use mdbook_preprocessor::book::{Book, BookItem};
+use mdbook_preprocessor::errors::Error;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Labels of the "See also" paragraphs and export of the graph of the
+/// relations between recommendations
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// "See also" as default
+    see_also: Option<String>,
+    /// Labels of the relations (`related`, `refines` and `supersedes`),
+    /// "related to", "refines" and "supersedes" as default
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    /// Formats of the exported graph, not exported as default
+    #[serde(default)]
+    graph: Vec<GraphFormat>,
+    /// Output directory, relative to the book root (see [`output_dir`])
+    dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum GraphFormat {
+    Dot,
+    Json,
+}
+
+impl Config {
+    fn label<'a>(&'a self, relation: &'a str) -> &'a str {
+        match (self.labels.get(relation), relation) {
+            (Some(label), _) => label,
+            (None, "related") => "related to",
+            (None, relation) => relation,
+        }
+    }
+}
+
+/// Offset of the closing tag of the `div` whose opening tag starts at `start`
+fn closing_tag(content: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for m in DIV_TAG_RE.find_iter(&content[start..]) {
+        if m.as_str().starts_with("</") {
+            depth -= 1;
+            if depth == 0 {
+                return Some(start + m.start());
+            }
+        } else {
+            depth += 1;
+        }
+    }
+    None
+}
+
+/// Appends a "See also" paragraph to the recommendations related to others,
+/// linking to them.
+pub(crate) fn render(book: &mut Book, config: &Config, checklist: &Checklist) {
+    let paths: HashMap<&str, &Path> = checklist
+        .chapters()
+        .flat_map(|(_, path, entries)| entries.iter().map(move |e| (&e.name as &str, path)))
+        .collect();
+    let by_path: HashMap<&Path, &[CheckEntry]> = checklist
+        .chapters()
+        .map(|(_, path, entries)| (path, entries))
+        .collect();
+    let see_also = config.see_also.as_deref().unwrap_or("See also");
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(chapter) = item else {
+            return;
+        };
+        let Some(path) = chapter.path.clone() else {
+            return;
+        };
+        let Some(entries) = by_path.get(&path as &Path) else {
+            return;
+        };
+        let tags = id_tags(&chapter.content);
+        let mut paragraphs = Vec::new();
+        for entry in entries.iter() {
+            let mut links = Vec::new();
+            for (relation, ids) in entry.meta.relations() {
+                for id in ids {
+                    if let Some(target) = paths.get(id as &str) {
+                        links.push(format!(
+                            "{} [{id}]({})",
+                            config.label(relation),
+                            href(&path, target, id)
+                        ));
+                    }
+                }
+            }
+            if links.is_empty() {
+                continue;
+            }
+            let Some(tag) = tags.get(&entry.name) else {
+                continue;
+            };
+            if let Some(offset) = closing_tag(&chapter.content, tag.range.start) {
+                let paragraph = format!("\n**{see_also}:** {}\n\n", links.join(", "));
+                paragraphs.push((offset, paragraph));
+            }
+        }
+        // from the end, for the offsets of the others to remain valid
+        paragraphs.sort_by_key(|(offset, _)| Reverse(*offset));
+        for (offset, paragraph) in paragraphs {
+            chapter.content.insert_str(offset, &paragraph);
+        }
+    });
+}
+
+#[derive(Debug, Serialize)]
+struct Node<'a> {
+    id: &'a str,
+    #[serde(rename = "type")]
+    typ: Option<&'a str>,
+    title: &'a str,
+    path: &'a Path,
+}
+
+#[derive(Debug, Serialize)]
+struct Edge<'a> {
+    from: &'a str,
+    to: &'a str,
+    relation: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Graph<'a> {
+    nodes: Vec<Node<'a>>,
+    edges: Vec<Edge<'a>>,
+}
+
+fn graph(checklist: &Checklist) -> Graph<'_> {
+    let mut graph = Graph {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+    };
+    for (_, path, entries) in checklist.chapters() {
+        for entry in entries {
+            graph.nodes.push(Node {
+                id: &entry.name,
+                typ: entry.typ.as_deref(),
+                title: &entry.title,
+                path,
+            });
+            for (relation, ids) in entry.meta.relations() {
+                graph.edges.extend(ids.iter().map(|id| Edge {
+                    from: &entry.name,
+                    to: id,
+                    relation,
+                }));
+            }
+        }
+    }
+    graph
+}
+
+/// Escapes a text to be written in a quoted DOT string
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+fn to_dot(graph: &Graph) -> String {
+    let mut dot = String::from("digraph recommendations {\n");
+    for node in &graph.nodes {
+        dot.push_str(&format!(
+            "  {} [label=\"{}\\n{}\"];\n",
+            quoted(node.id),
+            escape(node.id),
+            escape(node.title)
+        ));
+    }
+    for edge in &graph.edges {
+        dot.push_str(&format!(
+            "  {} -> {} [label={}];\n",
+            quoted(edge.from),
+            quoted(edge.to),
+            quoted(edge.relation)
+        ));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Writes the graph of the relations in every configured format.
+pub(crate) fn export(
+    config: &Config,
+    checklist: &Checklist,
+    root: &Path,
+    build_dir: &Path,
+) -> Result<(), Error> {
+    if config.graph.is_empty() {
+        return Ok(());
+    }
+    let dir = output_dir(config.dir.as_deref(), root, build_dir);
+    fs::create_dir_all(&dir)?;
+    let graph = graph(checklist);
+    for format in &config.graph {
+        match format {
+            GraphFormat::Dot => fs::write(dir.join("relations.dot"), to_dot(&graph))?,
+            GraphFormat::Json => fs::write(
+                dir.join("relations.json"),
+                serde_json::to_string_pretty(&graph)?,
+            )?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook_preprocessor::book::Chapter;
+
+    fn checklist() -> Checklist {
+        Checklist::from_entries(
+            [
+                ("unsafe/memory.md", "MEM-FORGET", None),
+                ("unsafe/memory.md", "MEM-FORGET-LINT", Some("MEM-FORGET")),
+                ("devenv.md", "DENV-LINTER", None),
+            ]
+            .map(|(path, id, refines)| {
+                let mut entry = CheckEntry::new(id, Some("Rule"), &format!("Title \"{id}\""));
+                if let Some(refines) = refines {
+                    entry.meta.refines = vec![refines.to_string()];
+                    entry.meta.related = vec!["DENV-LINTER".to_string()];
+                }
+                (path, entry)
+            }),
+        )
+    }
+
+    #[test]
+    fn see_also() {
+        let content = r#"<div class="reco" id="MEM-FORGET" type="Rule" title="A">
+</div>
+
+<div class="reco" id="MEM-FORGET-LINT" type="Rule" title="B">
+
+Some text.
+
+<div class="warning">
+</div>
+
+</div>
+"#;
+        let mut book = Book::new_with_items(vec![BookItem::Chapter(Chapter::new(
+            "Memory",
+            content.to_string(),
+            "unsafe/memory.md",
+            vec![],
+        ))]);
+        let config: Config = toml::from_str("labels = { refines = \"Refines\" }").unwrap();
+        render(&mut book, &config, &checklist());
+        assert_eq!(
+            r#"<div class="reco" id="MEM-FORGET" type="Rule" title="A">
+</div>
+
+<div class="reco" id="MEM-FORGET-LINT" type="Rule" title="B">
+
+Some text.
+
+<div class="warning">
+</div>
+
+
+**See also:** related to [DENV-LINTER](../devenv.md#DENV-LINTER), Refines [MEM-FORGET](#MEM-FORGET)
+
+</div>
+"#,
+            book.chapters().next().unwrap().content
+        );
+    }
+
+    #[test]
+    fn closing_tag_by_id() {
+        let content = "<div data-id=\"A\">\n</div>\n\n<div class=\"reco\" id=\"A\">\n</div>\n";
+        let start = id_tags(content)["A"].range.start;
+        assert_eq!(
+            Some(content.rfind("</div>").unwrap()),
+            closing_tag(content, start)
+        );
+    }
+
+    #[test]
+    fn quoted_attributes() {
+        let content = "<div class='reco' id='MEM-FORGET-LINT' title=\"<div> > </div>\">\n\
+                       Some text.\n</div>\n";
+        let mut book = Book::new_with_items(vec![BookItem::Chapter(Chapter::new(
+            "Memory",
+            content.to_string(),
+            "unsafe/memory.md",
+            vec![],
+        ))]);
+        render(&mut book, &Config::default(), &checklist());
+        assert_eq!(
+            "<div class='reco' id='MEM-FORGET-LINT' title=\"<div> > </div>\">\n\
+             Some text.\n\n**See also:** related to [DENV-LINTER](../devenv.md#DENV-LINTER), \
+             refines [MEM-FORGET](#MEM-FORGET)\n\n</div>\n",
+            book.chapters().next().unwrap().content
+        );
+    }
+
+    #[test]
+    fn dot() {
+        let checklist = checklist();
+        assert_eq!(
+            r#"digraph recommendations {
+  "MEM-FORGET" [label="MEM-FORGET\nTitle \"MEM-FORGET\""];
+  "MEM-FORGET-LINT" [label="MEM-FORGET-LINT\nTitle \"MEM-FORGET-LINT\""];
+  "DENV-LINTER" [label="DENV-LINTER\nTitle \"DENV-LINTER\""];
+  "MEM-FORGET-LINT" -> "DENV-LINTER" [label="related"];
+  "MEM-FORGET-LINT" -> "MEM-FORGET" [label="refines"];
+}
+"#,
+            to_dot(&graph(&checklist))
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use mdbook_markdown::pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use mdbook_preprocessor::book::{Book, BookItem, Chapter, SectionNumber};
+use mdbook_preprocessor::errors::Error;
+use std::fs;
+use std::path::Path;
+
+// A minimal reader of `SUMMARY.md` for the subcommands working directly on
+// the book sources, without `mdbook`. It handles prefix, numbered, nested and
+// suffix chapters, draft chapters, part titles and separators; chapters are
+// read as is (no `{{#include}}` expansion).
+
+/// Loads the book described by `SUMMARY.md` in the source directory.
+pub(crate) fn load_book(src_dir: &Path) -> Result<Book, Error> {
+    let summary_path = src_dir.join("SUMMARY.md");
+    let summary = fs::read_to_string(&summary_path)
+        .map_err(|e| Error::msg(format!("Cannot read {}: {e}", summary_path.display())))?;
+    let items = parse_summary(&summary, |path| {
+        let path = src_dir.join(path);
+        fs::read_to_string(&path)
+            .map_err(|e| Error::msg(format!("Cannot read {}: {e}", path.display())))
+    })?;
+    Ok(Book::new_with_items(items))
+}
+
+fn parse_summary<F>(summary: &str, mut read: F) -> Result<Vec<BookItem>, Error>
+where
+    F: FnMut(&str) -> Result<String, Error>,
+{
+    // items of the current list at each nesting level, the root being first
+    let mut levels: Vec<Vec<BookItem>> = vec![Vec::new()];
+    let mut list_depth: usize = 0;
+    let mut section: Vec<u32> = Vec::new();
+    let mut parents: Vec<String> = Vec::new();
+    let mut seen_title = false;
+    let mut text: Option<String> = None;
+    let mut link: Option<String> = None;
+
+    for event in Parser::new(summary) {
+        match event {
+            Event::Start(Tag::Heading { .. }) | Event::Start(Tag::Link { .. })
+                if text.is_none() =>
+            {
+                if let Event::Start(Tag::Link { dest_url, .. }) = event {
+                    link = Some(dest_url.to_string());
+                }
+                text = Some(String::new());
+            }
+            Event::Text(s) | Event::Code(s) => {
+                if let Some(text) = &mut text {
+                    text.push_str(&s);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                let title = text.take().unwrap_or_default();
+                // the first heading is the title of the summary
+                if seen_title {
+                    levels[0].push(BookItem::PartTitle(title));
+                }
+                seen_title = true;
+            }
+            Event::End(TagEnd::Link) => {
+                let name = text.take().unwrap_or_default();
+                let dest = link.take().unwrap_or_default();
+                let parent_names = parents
+                    .iter()
+                    .take(list_depth.saturating_sub(1))
+                    .cloned()
+                    .collect();
+                let mut chapter = if dest.is_empty() {
+                    Chapter::new_draft(&name, parent_names)
+                } else {
+                    let dest = dest.trim_start_matches("./");
+                    Chapter::new(&name, read(dest)?, dest, parent_names)
+                };
+                if list_depth > 0 {
+                    section.truncate(list_depth);
+                    if section.len() < list_depth {
+                        section.push(1);
+                    } else {
+                        section[list_depth - 1] += 1;
+                    }
+                    chapter.number = Some(SectionNumber::new(section.clone()));
+                    parents.truncate(list_depth - 1);
+                    parents.push(name);
+                }
+                levels.last_mut().unwrap().push(BookItem::Chapter(chapter));
+            }
+            Event::Start(Tag::List(_)) => {
+                list_depth += 1;
+                if list_depth > 1 {
+                    levels.push(Vec::new());
+                }
+            }
+            Event::End(TagEnd::List(_)) => {
+                if list_depth > 1 {
+                    let sub_items = levels.pop().unwrap();
+                    if let Some(BookItem::Chapter(parent)) = levels.last_mut().unwrap().last_mut() {
+                        parent.sub_items = sub_items;
+                    }
+                }
+                list_depth -= 1;
+            }
+            Event::Rule => levels[0].push(BookItem::Separator),
+            _ => {}
+        }
+    }
+    Ok(levels.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let summary = r#"# Summary
+
+[Introduction](introduction.md)
+
+# Lifecycle
+
+- [Development environment](devenv.md)
+- [Libraries](./libraries.md)
+
+# Language
+
+- [Naming](naming.md)
+<!-- - [Macros](macros.md) -->
+- [Unsafe Rust](unsafe.md)
+  - [Generalities](unsafe/generalities.md)
+  - [Draft]()
+- [Standard library](standard.md)
+
+---
+
+[License](LICENSE.md)
+"#;
+        let items = parse_summary(summary, |path| Ok(format!("content of {path}"))).unwrap();
+        let book = Book::new_with_items(items);
+        let chapters: Vec<(String, Vec<String>, Option<String>)> = book
+            .chapters()
+            .map(|c| {
+                (
+                    c.to_string(),
+                    c.parent_names.clone(),
+                    c.path.as_ref().map(|p| p.display().to_string()),
+                )
+            })
+            .collect();
+        let s = |s: &str| s.to_string();
+        assert_eq!(
+            vec![
+                (s("Introduction"), vec![], Some(s("introduction.md"))),
+                (
+                    s("1. Development environment"),
+                    vec![],
+                    Some(s("devenv.md"))
+                ),
+                (s("2. Libraries"), vec![], Some(s("libraries.md"))),
+                (s("3. Naming"), vec![], Some(s("naming.md"))),
+                (s("4. Unsafe Rust"), vec![], Some(s("unsafe.md"))),
+                (
+                    s("4.1. Generalities"),
+                    vec![s("Unsafe Rust")],
+                    Some(s("unsafe/generalities.md"))
+                ),
+                (s("5. Standard library"), vec![], Some(s("standard.md"))),
+                (s("License"), vec![], Some(s("LICENSE.md"))),
+            ],
+            chapters
+        );
+        assert!(matches!(&book.items[1], BookItem::PartTitle(t) if t == "Lifecycle"));
+        assert!(matches!(&book.items[8], BookItem::Separator));
+        let BookItem::Chapter(devenv) = &book.items[2] else {
+            panic!("not a chapter")
+        };
+        assert_eq!("content of devenv.md", devenv.content);
+        let BookItem::Chapter(unsafe_rust) = &book.items[6] else {
+            panic!("not a chapter")
+        };
+        assert!(
+            matches!(&unsafe_rust.sub_items[1], BookItem::Chapter(c) if c.is_draft_chapter() && c.name == "Draft")
+        );
+    }
+}
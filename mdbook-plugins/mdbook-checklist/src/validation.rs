@@ -0,0 +1,293 @@
+use crate::checklist::Checklist;
+use mdbook_preprocessor::errors::Error;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Accepted values of the `type` attribute (any value as default)
+    types: Option<Vec<String>>,
+    /// Naming scheme of ids, by chapter path (or parent directory)
+    #[serde(default)]
+    ids: BTreeMap<PathBuf, IdScheme>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum IdScheme {
+    /// Ids must start with one of the prefixes
+    Prefixes(Vec<String>),
+    /// Ids must match the regular expression
+    Pattern(String),
+}
+
+enum IdMatcher<'a> {
+    Prefixes(&'a [String]),
+    Pattern(Regex),
+}
+
+impl IdMatcher<'_> {
+    fn is_match(&self, id: &str) -> bool {
+        match self {
+            IdMatcher::Prefixes(prefixes) => prefixes.iter().any(|p| id.starts_with(p as &str)),
+            IdMatcher::Pattern(re) => re.is_match(id),
+        }
+    }
+}
+
+impl fmt::Display for IdMatcher<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdMatcher::Prefixes(prefixes) => write!(f, "prefixes: {}", prefixes.join(", ")),
+            IdMatcher::Pattern(re) => write!(f, "pattern: {re}"),
+        }
+    }
+}
+
+impl Config {
+    fn id_matchers(&self) -> Result<Vec<(&Path, IdMatcher<'_>)>, Error> {
+        self.ids
+            .iter()
+            .map(|(path, scheme)| {
+                let matcher = match scheme {
+                    IdScheme::Prefixes(prefixes) => IdMatcher::Prefixes(prefixes),
+                    IdScheme::Pattern(pattern) => {
+                        IdMatcher::Pattern(Regex::new(pattern).map_err(|e| {
+                            Error::msg(format!("Invalid id pattern for {}: {e}", path.display()))
+                        })?)
+                    }
+                };
+                Ok((path as &Path, matcher))
+            })
+            .collect()
+    }
+}
+
+/// Naming scheme of the most specific configured path containing the chapter
+fn id_matcher<'a, 'b>(
+    matchers: &'b [(&'a Path, IdMatcher<'a>)],
+    chap_path: &Path,
+) -> Option<(&'a Path, &'b IdMatcher<'a>)> {
+    matchers
+        .iter()
+        .filter(|(path, _)| chap_path.starts_with(path))
+        .max_by_key(|(path, _)| path.components().count())
+        .map(|(path, matcher)| (*path, matcher))
+}
+
+/// A malformed entry, located by its chapter path and line, and when known by
+/// its column and the offending source line
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Violation {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub snippet: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{column}")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n    {snippet}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the collected entries: unique and non-empty ids following the naming
+/// scheme of their chapter, non-empty titles and accepted types.
+pub(crate) fn check(config: &Config, checklist: &Checklist) -> Result<Vec<Violation>, Error> {
+    let matchers = config.id_matchers()?;
+    let mut violations = Vec::new();
+    let mut seen: HashMap<&str, (&Path, usize)> = HashMap::new();
+    let known: HashSet<&str> = checklist
+        .chapters()
+        .flat_map(|(_, _, entries)| entries.iter().map(|e| &e.name as &str))
+        .collect();
+    for (_, chap_path, entries) in checklist.chapters() {
+        let matcher = id_matcher(&matchers, chap_path);
+        for entry in entries {
+            let violation = |message: String| Violation {
+                path: chap_path.to_path_buf(),
+                line: entry.line,
+                message,
+                ..Default::default()
+            };
+            if entry.name.trim().is_empty() {
+                violations.push(violation("recommendation with an empty id".to_string()));
+            } else if let Some((path, line)) = seen.get(&entry.name as &str) {
+                violations.push(violation(format!(
+                    "duplicate id \"{}\" (first defined at {}:{})",
+                    entry.name,
+                    path.display(),
+                    line
+                )));
+            } else {
+                seen.insert(&entry.name, (chap_path, entry.line));
+            }
+            if let Some((scheme_path, matcher)) = matcher {
+                if !matcher.is_match(&entry.name) {
+                    violations.push(violation(format!(
+                        "id \"{}\" does not follow the naming scheme of {} ({matcher})",
+                        entry.name,
+                        scheme_path.display()
+                    )));
+                }
+            }
+            if entry.title.trim().is_empty() {
+                violations.push(violation(format!(
+                    "recommendation \"{}\" has an empty title",
+                    entry.name
+                )));
+            }
+            for (relation, ids) in entry.meta.relations() {
+                for id in ids {
+                    if id == &entry.name {
+                        violations.push(violation(format!(
+                            "recommendation \"{id}\" references itself in \"{relation}\""
+                        )));
+                    } else if !known.contains(id as &str) {
+                        violations.push(violation(format!(
+                            "recommendation \"{}\" references unknown id \"{id}\" in \"{relation}\"",
+                            entry.name
+                        )));
+                    }
+                }
+            }
+            if let (Some(types), Some(typ)) = (&config.types, &entry.typ) {
+                if !types.contains(typ) {
+                    violations.push(violation(format!(
+                        "recommendation \"{}\" has unknown type \"{typ}\" (expected one of: {})",
+                        entry.name,
+                        types.join(", ")
+                    )));
+                }
+            }
+        }
+    }
+    Ok(violations)
+}
+
+/// Turns a list of violations into an error listing all of them.
+pub(crate) fn into_result(violations: Vec<Violation>) -> Result<(), Error> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!("{} invalid recommendation(s):", violations.len());
+    for violation in violations {
+        message.push_str(&format!("\n  {violation}"));
+    }
+    Err(Error::msg(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::CheckEntry;
+
+    fn entry(name: &str, typ: &str, title: &str, line: usize) -> CheckEntry {
+        CheckEntry {
+            line,
+            ..CheckEntry::new(name, Some(typ), title)
+        }
+    }
+
+    #[test]
+    fn violations() {
+        let mut checklist = Checklist::new();
+        let ch1 = PathBuf::from("ch1.md");
+        let ch2 = PathBuf::from("ch2.md");
+        checklist.insert("Ch 1", &ch1, entry("A", "Rule", "Title", 3));
+        checklist.insert("Ch 1", &ch1, entry("B", "Rule", " ", 10));
+        checklist.insert("Ch 2", &ch2, entry("A", "Rule", "Copy", 7));
+        checklist.insert("Ch 2", &ch2, entry("C", "Rul", "Typo", 12));
+        let config = Config {
+            types: Some(vec!["Rule".to_string(), "Recommendation".to_string()]),
+            ..Config::default()
+        };
+        let messages: Vec<String> = check(&config, &checklist)
+            .unwrap()
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "ch1.md:10: recommendation \"B\" has an empty title",
+                "ch2.md:7: duplicate id \"A\" (first defined at ch1.md:3)",
+                "ch2.md:12: recommendation \"C\" has unknown type \"Rul\" (expected one of: Rule, Recommendation)",
+            ],
+            messages
+        );
+        assert!(check(&Config::default(), &checklist).unwrap().len() == 2);
+    }
+
+    #[test]
+    fn relations() {
+        let mut checklist = Checklist::new();
+        let memory = PathBuf::from("unsafe/memory.md");
+        let mut lint = entry("MEM-FORGET-LINT", "Recommendation", "T", 2);
+        lint.meta.refines = vec!["MEM-FORGET".to_string(), "MEM-FORGOT".to_string()];
+        lint.meta.related = vec!["MEM-FORGET-LINT".to_string()];
+        checklist.insert("Memory", &memory, entry("MEM-FORGET", "Rule", "T", 1));
+        checklist.insert("Memory", &memory, lint);
+        let messages: Vec<String> = check(&Config::default(), &checklist)
+            .unwrap()
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "unsafe/memory.md:2: recommendation \"MEM-FORGET-LINT\" references itself in \"related\"",
+                "unsafe/memory.md:2: recommendation \"MEM-FORGET-LINT\" references unknown id \"MEM-FORGOT\" in \"refines\"",
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn naming_scheme() {
+        let mut checklist = Checklist::new();
+        let standard = PathBuf::from("standard.md");
+        let memory = PathBuf::from("unsafe/memory.md");
+        let ffi = PathBuf::from("unsafe/ffi.md");
+        checklist.insert("Std", &standard, entry("LANG-SYNC-TRAITS", "Rule", "T", 1));
+        checklist.insert("Std", &standard, entry("MEM-MUT-REC-RC", "Rule", "T", 2));
+        checklist.insert("Memory", &memory, entry("MEM-FORGET", "Rule", "T", 3));
+        checklist.insert("FFI", &ffi, entry("FFI-CKNONROBUST", "Rule", "T", 4));
+        checklist.insert("FFI", &ffi, entry("LANG-UNSAFE", "Rule", "T", 5));
+        let config: Config = toml::from_str(
+            r#"
+            [ids]
+            "standard.md" = ["LANG-"]
+            "unsafe" = "^(MEM|FFI|UNSAFE)-"
+            "unsafe/memory.md" = ["MEM-"]
+            "#,
+        )
+        .unwrap();
+        let messages: Vec<String> = check(&config, &checklist)
+            .unwrap()
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "standard.md:2: id \"MEM-MUT-REC-RC\" does not follow the naming scheme of standard.md (prefixes: LANG-)",
+                "unsafe/ffi.md:5: id \"LANG-UNSAFE\" does not follow the naming scheme of unsafe (pattern: ^(MEM|FFI|UNSAFE)-)",
+            ],
+            messages
+        );
+
+        let config: Config = toml::from_str("[ids]\n\"unsafe\" = \"(\"").unwrap();
+        assert!(check(&config, &checklist).is_err());
+    }
+}
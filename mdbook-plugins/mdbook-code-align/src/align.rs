@@ -1,17 +1,113 @@
+use crate::rustfmt;
 use crate::semindent::{self, Element};
-use anyhow::{anyhow, Ok};
+use anyhow::anyhow;
 use markdown::{mdast::Node, to_mdast, ParseOptions};
 use mdbook_preprocessor::{
     book::{Book, BookItem},
     Preprocessor, PreprocessorContext,
 };
+use serde::Deserialize;
 
 pub(super) struct Align;
 
-fn get_content_mut(item: &mut BookItem) -> Vec<&mut String> {
+/// Options of `[preprocessor.code-align]`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+struct Config {
+    /// Width of the tabs of the indentation, expanded before aligning
+    tab_width: usize,
+    /// Number of spaces of each indentation level of the aligned blocks
+    indent: usize,
+    /// Keep the relative indentation of the aligned blocks, only stripping
+    /// their common prefix (overrides `indent`)
+    preserve_indent: bool,
+    /// Languages of the blocks aligned without the `align` word, unless
+    /// they have the `noalign` word
+    languages: Vec<String>,
+    /// Format the Rust blocks with `rustfmt` instead of aligning them
+    rustfmt: Option<rustfmt::Config>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            tab_width: 4,
+            indent: 3,
+            preserve_indent: false,
+            languages: Vec::new(),
+            rustfmt: None,
+        }
+    }
+}
+
+impl Config {
+    fn indent(&self) -> Indent {
+        if self.preserve_indent {
+            Indent::Preserve
+        } else {
+            Indent::Width(self.indent)
+        }
+    }
+
+    /// Indentation of a block given the words of its info string, if aligned
+    fn block_indent(&self, words: &[&str]) -> Option<Result<Indent, String>> {
+        if words.contains(&"noalign") {
+            return None;
+        }
+        requested_indent(words, self.indent()).or_else(|| {
+            let lang = words.first()?;
+            self.languages
+                .iter()
+                .any(|l| l == lang)
+                .then_some(Ok(self.indent()))
+        })
+    }
+}
+
+/// Indentation of an aligned block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Indent {
+    /// Each level indented by this number of spaces
+    Width(usize),
+    /// Relative indentation kept, only the common prefix being stripped
+    Preserve,
+}
+
+/// Words of the info string of a block, separated by spaces or commas (the
+/// language being the first one)
+fn info_words<'a>(lang: Option<&'a str>, meta: Option<&'a str>) -> Vec<&'a str> {
+    [lang, meta]
+        .into_iter()
+        .flatten()
+        .flat_map(|s| s.split([' ', ',']))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Indentation requested by the `align` word of an info string, if any:
+/// `align` (`default`), `align=<width>` or `align=preserve`
+fn requested_indent(words: &[&str], default: Indent) -> Option<Result<Indent, String>> {
+    words
+        .iter()
+        .find_map(|word| match word.strip_prefix("align")? {
+            "" => Some(Ok(default)),
+            value => match value.strip_prefix('=')? {
+                "preserve" => Some(Ok(Indent::Preserve)),
+                width => Some(
+                    width
+                        .parse()
+                        .map(Indent::Width)
+                        .map_err(|_| format!("invalid alignment \"{word}\"")),
+                ),
+            },
+        })
+}
+
+/// Contents of the chapters, with their names
+fn get_content_mut(item: &mut BookItem) -> Vec<(&str, &mut String)> {
     match item {
         BookItem::Chapter(chapter) => {
-            let content: &mut String = &mut chapter.content;
+            let content = (&chapter.name as &str, &mut chapter.content);
             chapter
                 .sub_items
                 .iter_mut()
@@ -41,48 +137,78 @@ impl Preprocessor for Align {
         "align-preprocessor"
     }
 
-    fn run(&self, _ctx: &PreprocessorContext, mut book: Book) -> anyhow::Result<Book> {
-        for content in book.items.iter_mut().flat_map(get_content_mut) {
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> anyhow::Result<Book> {
+        let mut config: Config = ctx
+            .config
+            .get("preprocessor.code-align")?
+            .unwrap_or_default();
+        if let Some(rustfmt) = &mut config.rustfmt {
+            rustfmt.default_edition(ctx.config.rust.edition);
+        }
+        let rustfmt = config.rustfmt.as_ref();
+        for (chapter, content) in book.items.iter_mut().flat_map(get_content_mut) {
             let mut changes = Vec::new();
             let ast = to_mdast(content, &ParseOptions::default())
                 .map_err(|md_msg| anyhow!("{}", md_msg))?;
             visit_ast(&ast, &mut |n| {
-                if let Node::Code(code) = n {
-                    if code
-                        .meta
-                        .as_ref()
-                        .map(|s| s as &str)
-                        .unwrap_or_default()
-                        .split(' ')
-                        .any(|s| s == "align")
-                    {
-                        if let Some(p) = &code.position {
-                            let value = code.value.clone();
-                            changes.push((
-                                p.clone(),
-                                code.lang.clone(),
-                                code.meta.clone(),
-                                move |prefix| align(prefix, &value),
-                            ));
+                let Node::Code(code) = n else {
+                    return;
+                };
+                let Some(p) = &code.position else {
+                    return;
+                };
+                let warn = |message: &str| {
+                    eprintln!(
+                        "Warning: chapter \"{chapter}\", code block at line {}: {message}",
+                        p.start.line
+                    )
+                };
+                let words = info_words(code.lang.as_deref(), code.meta.as_deref());
+                let value = semindent::expand_tabs(&code.value, config.tab_width).into_owned();
+                // `rustfmt` is only looked for when a block is to be formatted
+                if let Some(rustfmt) =
+                    rustfmt.filter(|rustfmt| rustfmt.formats(&words) && rustfmt.available())
+                {
+                    match rustfmt::format(rustfmt, &ctx.root, &value) {
+                        Ok(formatted) => {
+                            // trailing blank lines kept as written
+                            let trailing = &value[value.trim_end_matches('\n').len()..];
+                            let formatted = formatted.trim_end_matches('\n').to_string() + trailing;
+                            changes.push((p.clone(), formatted, Indent::Preserve));
+                            return;
                         }
+                        Err(message) => warn(&format!(
+                            "not formatted by rustfmt: {}",
+                            message.lines().next().unwrap_or_default()
+                        )),
                     }
                 }
+                let Some(indent) = config.block_indent(&words) else {
+                    return;
+                };
+                let indent = indent.unwrap_or_else(|message| {
+                    warn(&message);
+                    config.indent()
+                });
+                for line in semindent::misaligned_lines(&value) {
+                    warn(&format!(
+                        "inconsistent indentation on line {}",
+                        p.start.line + line
+                    ));
+                }
+                changes.push((p.clone(), value, indent));
             });
-            changes.sort_by_key(|(pos, _, _, _)| pos.start.offset);
+            changes.sort_by_key(|(pos, _, _)| pos.start.offset);
             let mut new_content = String::new();
             let mut start = 0;
-            for (pos, lang, meta, new_code) in changes {
-                let last_line = content[0..pos.start.offset].lines().last().unwrap_or("");
-                let code_option = match (lang, meta) {
-                    (None, None) => "".to_string(),
-                    (None, Some(_)) => "".to_string(),
-                    (Some(l), None) => l,
-                    (Some(l), Some(m)) => format!("{l} {m}"),
-                };
+            for (pos, value, indent) in changes {
+                let line_start = content[0..pos.start.offset]
+                    .rfind('\n')
+                    .map_or(0, |i| i + 1);
+                let prefix = continuation_prefix(&content[line_start..pos.start.offset]);
+                let block = &content[pos.start.offset..pos.end.offset];
                 new_content.push_str(&content[start..pos.start.offset]);
-                new_content.push_str(&format!("```{}\n", code_option));
-                new_content.push_str(&new_code(last_line));
-                new_content.push_str(&format!("{last_line}```"));
+                new_content.push_str(&replace_body(block, &align(&prefix, &value, indent)));
                 start = pos.end.offset;
             }
             new_content.push_str(&content[start..content.len()]);
@@ -92,13 +218,70 @@ impl Preprocessor for Align {
     }
 }
 
-fn align(prefix: &str, content: &str) -> String {
-    let sem = semindent::parse_indented_text(content);
-    let mut unaligned = &sem;
-    if let (Some(Element::Subtext(sem)), None) = (sem.first(), sem.get(1)) {
-        unaligned = sem;
+/// Prefix of the lines following the opening fence, from the containers of the
+/// block before it: blockquote markers and whitespace are kept, list markers
+/// are replaced by spaces (e.g. `  ` for `- `)
+fn continuation_prefix(prefix: &str) -> String {
+    prefix
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || c == '>' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect()
+}
+
+/// Whether a line (with its container prefix) closes a block opened by `fence`
+fn is_closing_fence(line: &str, fence: &str) -> bool {
+    let line = line.trim_start_matches([' ', '\t', '>']);
+    let Some(fence_char) = fence.chars().next() else {
+        return false;
+    };
+    let len = line.len() - line.trim_start_matches(fence_char).len();
+    len >= fence.len() && line[len..].trim().is_empty()
+}
+
+/// Replaces the body of a fenced code block, keeping its opening line (fence
+/// and info string) and closing line as is, and its line endings. Blocks
+/// without closing fence (e.g. ended by their container) are left unchanged.
+fn replace_body(block: &str, body: &str) -> String {
+    let mut lines = block.split_inclusive('\n');
+    let opening = lines.next().unwrap_or_default();
+    let fence_len = opening.len() - opening.trim_start_matches(['`', '~']).len();
+    let closing = lines.next_back().unwrap_or_default();
+    if fence_len < 3 || !is_closing_fence(closing, &opening[0..fence_len]) {
+        return block.to_string();
+    }
+    let mut res = opening.to_string();
+    if opening.ends_with("\r\n") {
+        res.push_str(&body.replace('\n', "\r\n"));
+    } else {
+        res.push_str(body);
     }
-    semindent::to_string(unaligned, prefix, "   ")
+    res.push_str(closing);
+    res
+}
+
+fn align(prefix: &str, content: &str, indent: Indent) -> String {
+    let mut aligned = match indent {
+        Indent::Width(width) => {
+            let sem = semindent::parse_indented_text(content);
+            let mut unaligned = &sem;
+            if let (Some(Element::Subtext(sem)), None) = (sem.first(), sem.get(1)) {
+                unaligned = sem;
+            }
+            semindent::to_string(unaligned, prefix, &" ".repeat(width))
+        }
+        Indent::Preserve => semindent::strip_common_prefix(content, prefix),
+    };
+    // keep the trailing blank line, not seen as a line
+    if content.ends_with('\n') {
+        aligned.push_str(&format!("{}\n", prefix.trim_end()));
+    }
+    aligned
 }
 
 #[cfg(test)]
@@ -242,6 +425,168 @@ fin
         assert_eq!(actual_book, expected_book);
     }
 
+    #[test]
+    fn align_list() {
+        let content = r#"- ```rust align
+      fn main() {
+          a();
+      }
+  ```
+
+> 1. ```c align
+>        int a;
+>    ```
+"#;
+        let expected = r#"- ```rust align
+  fn main() {
+     a();
+  }
+  ```
+
+> 1. ```c align
+>    int a;
+>    ```
+"#;
+        let input_json: &[u8] = &exemple_book(content);
+        let expected_json: &[u8] = &exemple_book(expected);
+
+        let (ctx, book) = mdbook_preprocessor::parse_input(input_json).unwrap();
+        let (_, expected_book) = mdbook_preprocessor::parse_input(expected_json).unwrap();
+        let actual_book = Align.run(&ctx, book).unwrap();
+        assert_eq!(actual_book, expected_book);
+    }
+
+    #[test]
+    fn align_tabs() {
+        let content = "```c align\n\tint main() {\n\t    return 0;\n  \t}\n```\n";
+        let expected = "```c align\nint main() {\n   return 0;\n}\n```\n";
+        let input_json: &[u8] = &exemple_book(content);
+        let expected_json: &[u8] = &exemple_book(expected);
+
+        let (ctx, book) = mdbook_preprocessor::parse_input(input_json).unwrap();
+        let (_, expected_book) = mdbook_preprocessor::parse_input(expected_json).unwrap();
+        let actual_book = Align.run(&ctx, book).unwrap();
+        assert_eq!(actual_book, expected_book);
+    }
+
+    #[test]
+    fn indent() {
+        let content = r#"```rust align=4
+    fn main() {
+      println!("Hello, World")
+    }
+```
+
+```rust align=preserve
+    fn main() {
+      println!("Hello, World")
+
+    }
+```
+
+> ```rust align
+>     fn main() {
+>       println!("Hello, World");
+> 
+>       println!("Hello, World")
+>     }
+> 
+> ```
+"#;
+        let expected = r#"```rust align=4
+fn main() {
+    println!("Hello, World")
+}
+```
+
+```rust align=preserve
+fn main() {
+  println!("Hello, World")
+
+}
+```
+
+> ```rust align
+> fn main() {
+>    println!("Hello, World");
+>
+>    println!("Hello, World")
+> }
+>
+> ```
+"#;
+        let input_json: &[u8] = &exemple_book(content);
+        let expected_json: &[u8] = &exemple_book(expected);
+
+        let (ctx, book) = mdbook_preprocessor::parse_input(input_json).unwrap();
+        let (_, expected_book) = mdbook_preprocessor::parse_input(expected_json).unwrap();
+        let actual_book = Align.run(&ctx, book).unwrap();
+        assert_eq!(actual_book, expected_book);
+    }
+
+    #[test]
+    fn fences() {
+        let content = "~~~rust align\n    a\n~~~\n\n\
+                       ````markdown align title=\"x\"\n    ```rust\n    b\n    ```\n`````\n\n\
+                       ```\tc align\r\n  c\r\n```\r\n\n\
+                       text\n```rust align\n    d\n";
+        let expected = "~~~rust align\na\n~~~\n\n\
+                        ````markdown align title=\"x\"\n```rust\nb\n```\n`````\n\n\
+                        ```\tc align\r\nc\r\n```\r\n\n\
+                        text\n```rust align\n    d\n";
+        let input_json: &[u8] = &exemple_book(content);
+        let expected_json: &[u8] = &exemple_book(expected);
+
+        let (ctx, book) = mdbook_preprocessor::parse_input(input_json).unwrap();
+        let (_, expected_book) = mdbook_preprocessor::parse_input(expected_json).unwrap();
+        let actual_book = Align.run(&ctx, book).unwrap();
+        assert_eq!(actual_book, expected_book);
+    }
+
+    #[test]
+    fn requested() {
+        let default = Indent::Width(3);
+        let requested = |info: &str| {
+            let words: Vec<&str> = info.split(' ').collect();
+            requested_indent(&words, default)
+        };
+        assert_eq!(None, requested(""));
+        assert_eq!(None, requested("aligned noalign"));
+        assert_eq!(Some(Ok(default)), requested("title=x align"));
+        assert_eq!(Some(Ok(Indent::Width(2))), requested("align=2"));
+        assert_eq!(Some(Ok(Indent::Preserve)), requested("align=preserve"));
+        assert_eq!(
+            Some(Err("invalid alignment \"align=x\"".to_string())),
+            requested("align=x")
+        );
+    }
+
+    #[test]
+    fn languages() {
+        let config = Config {
+            languages: vec!["rust".to_string(), "c".to_string()],
+            ..Default::default()
+        };
+        let indent = |lang, meta| config.block_indent(&info_words(lang, meta));
+        assert_eq!(Some(Ok(Indent::Width(3))), indent(Some("rust"), None));
+        assert_eq!(
+            Some(Ok(Indent::Width(3))),
+            indent(Some("c"), Some("title=x"))
+        );
+        assert_eq!(
+            Some(Ok(Indent::Width(3))),
+            indent(Some("rust,noplaypen"), None)
+        );
+        assert_eq!(None, indent(Some("rust,noalign"), None));
+        assert_eq!(None, indent(Some("rust"), Some("noalign align=4")));
+        assert_eq!(None, indent(Some("shell"), None));
+        assert_eq!(
+            Some(Ok(Indent::Width(4))),
+            indent(Some("shell"), Some("align=4"))
+        );
+        assert_eq!(None, indent(None, None));
+    }
+
     #[test]
     fn no_align() {
         let content = r#"# Ceci est le titre
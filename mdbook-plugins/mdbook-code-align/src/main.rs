@@ -7,6 +7,7 @@ use std::io;
 use std::process;
 
 mod align;
+mod rustfmt;
 mod semindent;
 
 // pub fn make_app() -> Command {
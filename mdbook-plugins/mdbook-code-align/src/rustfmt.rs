@@ -0,0 +1,386 @@
+use mdbook_preprocessor::config::RustEdition;
+use serde::Deserialize;
+use std::cell::OnceCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Options of `[preprocessor.code-align.rustfmt]`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub(crate) struct Config {
+    /// Path of the `rustfmt` executable
+    path: PathBuf,
+    /// Edition of the formatted code (the `rust.edition` of the book as
+    /// default, otherwise 2021)
+    edition: Option<String>,
+    /// `rustfmt.toml` to use, relative to the book root (otherwise looked up
+    /// from the book root)
+    config_path: Option<PathBuf>,
+    /// Words of the info string of the blocks not to format
+    skip: Vec<String>,
+    /// Whether `rustfmt` can be run, checked on the first block to format
+    #[serde(skip)]
+    available: OnceCell<bool>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            path: PathBuf::from("rustfmt"),
+            edition: None,
+            config_path: None,
+            skip: vec!["ignore".to_string(), "noformat".to_string()],
+            available: OnceCell::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Whether a block with these info string words is formatted
+    pub(crate) fn formats(&self, words: &[&str]) -> bool {
+        words.first() == Some(&"rust")
+            && !words.iter().any(|word| self.skip.iter().any(|s| s == word))
+    }
+
+    /// Uses the edition of the book when none is given.
+    pub(crate) fn default_edition(&mut self, edition: Option<RustEdition>) {
+        if self.edition.is_none() {
+            self.edition = edition
+                .and_then(|edition| serde_json::to_value(edition).ok())
+                .and_then(|value| value.as_str().map(str::to_string));
+        }
+    }
+
+    /// Whether `rustfmt` can be run, warning if not. It is only run once, on
+    /// the first call.
+    pub(crate) fn available(&self) -> bool {
+        *self.available.get_or_init(|| {
+            let available = Command::new(&self.path)
+                .arg("--version")
+                .output()
+                .is_ok_and(|output| output.status.success());
+            if !available {
+                eprintln!(
+                    "Warning: cannot run {}, code blocks are not formatted",
+                    self.path.display()
+                );
+            }
+            available
+        })
+    }
+}
+
+/// Runs `rustfmt` on a piece of code.
+fn rustfmt(config: &Config, root: &Path, code: &str) -> Result<String, String> {
+    let mut command = Command::new(&config.path);
+    command.args(["--edition", config.edition.as_deref().unwrap_or("2021")]);
+    if let Some(config_path) = &config.config_path {
+        command.arg("--config-path").arg(root.join(config_path));
+    }
+    let mut child = command
+        .current_dir(root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {e}", config.path.display()))?;
+    // stdin is closed when dropped, before waiting for the output
+    child
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(code.as_bytes())
+        .map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).map_err(|e| e.to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Formats a piece of code, wrapping it in a function if it is not made of
+/// items (e.g. statements).
+fn format_snippet<F>(code: &str, format: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Result<String, String>,
+{
+    format(code).or_else(|error| {
+        let wrapped = format(&format!("fn main() {{\n{code}\n}}\n")).map_err(|_| error)?;
+        let lines: Vec<&str> = wrapped.lines().collect();
+        match lines[..] {
+            ["fn main() {", ref body @ .., "}"] => Ok(body
+                .iter()
+                .map(|line| format!("{}\n", line.strip_prefix("    ").unwrap_or(line)))
+                .collect()),
+            _ => Err("cannot unwrap the formatted code".to_string()),
+        }
+    })
+}
+
+/// Hidden (`# `) and escaped (`##`) lines of mdBook, formatted without their
+/// marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    Hidden,
+    Escaped,
+}
+
+fn strip_marker(line: &str) -> Option<(Marker, &str)> {
+    let trimmed = line.trim_start();
+    if trimmed == "#" {
+        Some((Marker::Hidden, ""))
+    } else if let Some(rest) = trimmed.strip_prefix("# ") {
+        Some((Marker::Hidden, rest))
+    } else {
+        // `##` shows as `#`
+        trimmed
+            .starts_with("##")
+            .then(|| (Marker::Escaped, &trimmed[1..]))
+    }
+}
+
+/// Pairs of indices of the lines of a longest common subsequence of `a` and
+/// `b`
+fn common_lines(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    // lengths of the longest common subsequences of the suffixes
+    let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = Vec::new();
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Indentation to remove from each line of formatted code, for the lines
+/// between hidden lines opening and closing a block (e.g. `# fn main() {` and
+/// `# }`), shown as if the block was not there.
+fn hidden_wrappers(lines: &[&str], markers: &[Option<Marker>]) -> Vec<usize> {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut dedent = vec![0; lines.len()];
+    for (start, line) in lines.iter().enumerate() {
+        if markers[start] != Some(Marker::Hidden) || !line.trim_end().ends_with('{') {
+            continue;
+        }
+        // the block ends at the next line indented as much as its first line
+        let Some(end) = (start + 1..lines.len())
+            .find(|&i| !lines[i].trim().is_empty() && indent(lines[i]) <= indent(line))
+        else {
+            continue;
+        };
+        if markers[end] != Some(Marker::Hidden) || !lines[end].trim_start().starts_with('}') {
+            continue;
+        }
+        let inner = lines[start + 1..end]
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| indent(line))
+            .min()
+            .unwrap_or_default();
+        for value in &mut dedent[start + 1..end] {
+            *value += inner.saturating_sub(indent(line));
+        }
+    }
+    dedent
+}
+
+/// Formats a piece of code with mdBook hidden lines, which are formatted too
+/// and hidden again. Fails if a hidden line is changed by the formatter.
+pub(crate) fn format_hidden<F>(code: &str, format: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Result<String, String>,
+{
+    let mut source = String::new();
+    // trimmed lines, with their marker
+    let mut lines = Vec::new();
+    for line in code.lines() {
+        match strip_marker(line) {
+            Some((marker, rest)) => {
+                lines.push((Some(marker), rest.trim()));
+                source.push_str(rest);
+            }
+            None => {
+                lines.push((None, line.trim()));
+                source.push_str(line);
+            }
+        }
+        source.push('\n');
+    }
+    let formatted = format_snippet(&source, format)?;
+    let formatted: Vec<&str> = formatted.lines().collect();
+    let texts: Vec<&str> = lines.iter().map(|(_, text)| *text).collect();
+    let trimmed: Vec<&str> = formatted.iter().map(|line| line.trim()).collect();
+    let mut markers = vec![None; formatted.len()];
+    let mut kept = vec![false; lines.len()];
+    for (i, j) in common_lines(&texts, &trimmed) {
+        markers[j] = lines[i].0;
+        kept[i] = true;
+    }
+    // hidden blank lines may be removed by the formatter
+    if let Some(((_, text), _)) = lines
+        .iter()
+        .zip(&kept)
+        .find(|((marker, text), kept)| marker.is_some() && !text.is_empty() && !**kept)
+    {
+        return Err(format!("hidden line \"{text}\" changed by rustfmt"));
+    }
+    let dedent = hidden_wrappers(&formatted, &markers);
+    let mut res = String::new();
+    for ((line, marker), dedent) in formatted.iter().zip(markers).zip(dedent) {
+        let content = line.trim_start();
+        let width = line.len() - content.len();
+        let indent = &line[dedent.min(width)..width];
+        match marker {
+            Some(Marker::Hidden) if content.is_empty() => res.push_str(&format!("{indent}#\n")),
+            Some(Marker::Hidden) => res.push_str(&format!("{indent}# {content}\n")),
+            Some(Marker::Escaped) => res.push_str(&format!("{indent}#{content}\n")),
+            None => res.push_str(&format!("{indent}{content}\n")),
+        }
+    }
+    Ok(res)
+}
+
+/// Formats a Rust code block with `rustfmt`.
+pub(crate) fn format(config: &Config, root: &Path, code: &str) -> Result<String, String> {
+    format_hidden(code, |code| rustfmt(config, root, code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A formatter removing the indentation, failing on lines starting with
+    /// `!`
+    fn fake(code: &str) -> Result<String, String> {
+        let mut res = String::new();
+        for line in code.lines() {
+            if line.starts_with('!') {
+                return Err("syntax error".to_string());
+            }
+            res.push_str(line.trim_start());
+            res.push('\n');
+        }
+        Ok(res)
+    }
+
+    #[test]
+    fn hidden_lines() {
+        let code = "  # use std::mem;\n  #\n  fn main() {}\n  ##[derive(Debug)]\n  struct A;\n";
+        assert_eq!(
+            "# use std::mem;\n#\nfn main() {}\n##[derive(Debug)]\nstruct A;\n",
+            format_hidden(code, fake).unwrap()
+        );
+        let code = "# fn main() {\nif a {\n}\n# }\n";
+        assert_eq!(
+            "# fn main() {\nif a {\n}\n# }\n",
+            format_hidden(code, fake).unwrap()
+        );
+        assert_eq!(
+            "hidden line \"use std::mem ;\" changed by rustfmt",
+            format_hidden("# use std::mem ;\nfn main() {}", |code| Ok(
+                code.replace(" ;", ";")
+            ))
+            .unwrap_err()
+        );
+    }
+
+    /// A formatter indenting the content of the blocks
+    fn indenting(code: &str) -> Result<String, String> {
+        let mut res = String::new();
+        let mut depth = 0;
+        for line in code.lines().map(str::trim) {
+            if line.starts_with('}') {
+                depth -= 1;
+            }
+            if !line.is_empty() {
+                res.push_str(&"    ".repeat(depth));
+            }
+            res.push_str(line);
+            res.push('\n');
+            if line.ends_with('{') {
+                depth += 1;
+            }
+        }
+        Ok(res)
+    }
+
+    #[test]
+    fn hidden_wrapper() {
+        let code = "struct A;\n\n# fn main() {\nif a {\nb();\n}\n# c();\n# }\n";
+        assert_eq!(
+            "struct A;\n\n# fn main() {\nif a {\n    b();\n}\n# c();\n# }\n",
+            format_hidden(code, indenting).unwrap()
+        );
+        let code = "# mod a {\n# fn main() {\nb();\n# }\n# }\n";
+        assert_eq!(code, format_hidden(code, indenting).unwrap());
+        // visible closing line
+        let code = "# fn main() {\nb();\n}\n";
+        assert_eq!(
+            "# fn main() {\n    b();\n}\n",
+            format_hidden(code, indenting).unwrap()
+        );
+    }
+
+    #[test]
+    fn formatted_blocks() {
+        let config = Config::default();
+        assert!(config.formats(&["rust", "noplaypen"]));
+        assert!(!config.formats(&["rust", "ignore"]));
+        assert!(!config.formats(&["rust", "noformat"]));
+        assert!(!config.formats(&["c"]));
+        assert!(!config.formats(&[]));
+    }
+
+    #[test]
+    fn editions() {
+        let mut config = Config::default();
+        config.default_edition(Some(RustEdition::E2024));
+        assert_eq!(Some("2024"), config.edition.as_deref());
+        let mut config: Config = serde_json::from_str(r#"{"edition": "2018"}"#).unwrap();
+        config.default_edition(Some(RustEdition::E2024));
+        assert_eq!(Some("2018"), config.edition.as_deref());
+    }
+
+    #[test]
+    fn checked_once() {
+        let config: Config = serde_json::from_str(r#"{"path": "/nonexistent/rustfmt"}"#).unwrap();
+        assert_eq!(None, config.available.get());
+        assert!(!config.available());
+        assert_eq!(Some(&false), config.available.get());
+    }
+
+    #[test]
+    fn wrapped() {
+        let format = |code: &str| {
+            if code.starts_with("fn main") {
+                Ok(code.replace("\nlet", "\n    let"))
+            } else {
+                Err("expected item".to_string())
+            }
+        };
+        assert_eq!(
+            "let a = 1;\nlet b = 2;\n",
+            format_snippet("let a = 1;\nlet b = 2;", format).unwrap()
+        );
+        assert_eq!("syntax error", format_snippet("!", fake).unwrap_err());
+    }
+}
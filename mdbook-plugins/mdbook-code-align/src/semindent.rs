@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::iter::{once, repeat_n};
 
 #[derive(Debug, PartialEq, Eq)]
@@ -7,13 +8,15 @@ pub enum Element<'a> {
 }
 
 impl<'a> Element<'a> {
-    fn to_string(&self, indent: &str, inc: &str) -> String {
+    /// Blank lines only get the `prefix`, without trailing whitespace.
+    fn to_string(&self, prefix: &str, indent: &str, inc: &str) -> String {
         match self {
+            Element::Line("") => format!("{}\n", prefix.trim_end()),
             Element::Line(s) => format!("{indent}{s}\n"),
             Element::Subtext(elements) => {
                 let mut res = String::new();
                 for e in elements {
-                    res.push_str(&e.to_string(&format!("{indent}{inc}"), inc));
+                    res.push_str(&e.to_string(prefix, &format!("{indent}{inc}"), inc));
                 }
                 res
             }
@@ -26,7 +29,7 @@ pub type TextStructure<'a> = Vec<Element<'a>>;
 pub fn to_string<'a>(strct: &TextStructure<'a>, indent: &str, inc: &str) -> String {
     let mut res = String::new();
     for e in strct {
-        res.push_str(&e.to_string(indent, inc));
+        res.push_str(&e.to_string(indent, indent, inc));
     }
     res
 }
@@ -36,6 +39,9 @@ enum IndentorToken<'a> {
     Indent,
     Line(&'a str),
     Dedent,
+    /// The indentation of this line (numbered from 1) is not a prefix of
+    /// the previous one nor one of the enclosing levels
+    Misaligned(usize),
 }
 
 fn strip_whispace_prefix(s: &str) -> &str {
@@ -46,7 +52,7 @@ fn strip_whispace_prefix(s: &str) -> &str {
 fn tokenize<'a>(text: &'a str) -> impl Iterator<Item = IndentorToken<'a>> {
     let mut indents: Vec<&'a str> = Vec::new();
     indents.push("");
-    text.lines().flat_map(move |line| {
+    text.lines().enumerate().flat_map(move |(i, line)| {
         let content = strip_whispace_prefix(line);
         if content.is_empty() {
             return [IndentorToken::Line("")].to_vec();
@@ -68,7 +74,8 @@ fn tokenize<'a>(text: &'a str) -> impl Iterator<Item = IndentorToken<'a>> {
             indents.push(indent);
             nbr_indent = 1;
         }
-        std::iter::repeat_n(IndentorToken::Dedent, nbr_dedent)
+        std::iter::repeat_n(IndentorToken::Misaligned(i + 1), nbr_indent)
+            .chain(repeat_n(IndentorToken::Dedent, nbr_dedent))
             .chain(repeat_n(IndentorToken::Indent, nbr_indent))
             .chain(once(IndentorToken::Line(content)))
             .collect()
@@ -85,6 +92,7 @@ fn get_tree<'a>(tokens: &mut impl Iterator<Item = IndentorToken<'a>>) -> TextStr
             }
             IndentorToken::Line(line) => res.push(Element::Line(line)),
             IndentorToken::Dedent => break,
+            IndentorToken::Misaligned(_) => {}
         }
     }
     res
@@ -94,6 +102,70 @@ pub fn parse_indented_text<'a>(text: &'a str) -> TextStructure<'a> {
     get_tree(&mut tokenize(text))
 }
 
+/// Lines (numbered from 1) whose indentation cannot be reconciled with the
+/// previous lines: neither deeper than the previous line nor back to an
+/// enclosing level.
+pub fn misaligned_lines(text: &str) -> Vec<usize> {
+    tokenize(text)
+        .filter_map(|token| match token {
+            IndentorToken::Misaligned(line) => Some(line),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Strips the whitespace prefix common to the non-blank lines, replacing it
+/// with `indent`.
+pub fn strip_common_prefix(text: &str, indent: &str) -> String {
+    let common = text
+        .lines()
+        .filter(|line| !strip_whispace_prefix(line).is_empty())
+        .map(|line| &line[0..(line.len() - strip_whispace_prefix(line).len())])
+        .reduce(|common, prefix| {
+            let len = common
+                .char_indices()
+                .zip(prefix.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(common.len().min(prefix.len()), |((i, _), _)| i);
+            &common[0..len]
+        })
+        .unwrap_or_default();
+    let mut res = String::new();
+    for line in text.lines() {
+        match line.strip_prefix(common) {
+            Some(line) if !strip_whispace_prefix(line).is_empty() => {
+                res.push_str(&format!("{indent}{line}\n"))
+            }
+            _ => res.push_str(&format!("{}\n", indent.trim_end())),
+        }
+    }
+    res
+}
+
+/// Expands the tabs of the indentation of every line to the next multiple of
+/// `tab_width` columns.
+pub fn expand_tabs(text: &str, tab_width: usize) -> Cow<'_, str> {
+    if !text.contains('\t') {
+        return Cow::Borrowed(text);
+    }
+    let mut res = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_start_matches([' ', '\t']);
+        let mut column = 0;
+        for c in line[0..(line.len() - content.len())].chars() {
+            let width = match c {
+                '\t' if tab_width > 0 => tab_width - column % tab_width,
+                '\t' => 0,
+                _ => 1,
+            };
+            res.extend(repeat_n(' ', width));
+            column += width;
+        }
+        res.push_str(content);
+    }
+    Cow::Owned(res)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -192,6 +264,31 @@ plaf
         );
     }
 
+    #[test]
+    fn tabs() {
+        let text = "int main() {\n\tif (x) {\n    \treturn 1;\n\t}\n}\tx\n";
+        assert_eq!(
+            "int main() {\n    if (x) {\n        return 1;\n    }\n}\tx\n",
+            expand_tabs(text, 4)
+        );
+        assert_eq!("a\n  b", expand_tabs("a\n \tb", 2));
+        assert!(matches!(expand_tabs("  a", 4), Cow::Borrowed("  a")));
+        assert_eq!(vec![3, 4], misaligned_lines(text));
+        assert!(misaligned_lines(&expand_tabs(text, 4)).is_empty());
+        assert_eq!(vec![2], misaligned_lines("   coucou\n plop"));
+    }
+
+    #[test]
+    fn common_prefix() {
+        assert_eq!(
+            "> a\n>   b\n>\n> c\n",
+            strip_common_prefix("    a\n      b\n  \n    c", "> ")
+        );
+        assert_eq!("a\n b\n", strip_common_prefix("\t a\n\t  b", ""));
+        assert_eq!("a\n", strip_common_prefix("a", ""));
+        assert_eq!("", strip_common_prefix("", "  "));
+    }
+
     #[test]
     fn pretty_print() {
         use Element::*;